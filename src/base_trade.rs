@@ -0,0 +1,213 @@
+use crate::combat_sim::*;
+use crate::*;
+
+// Frames to simulate each side destroying buildings
+const SKIRMISH_SIM_FRAMES: i32 = 8 * 24;
+// How long we assume a base trade to last before one side gives up
+const RACE_FRAMES: i32 = 20 * 24;
+// Frames to simulate our returning army against the raiders
+const DEFENSE_SIM_FRAMES: i32 = 8 * 24;
+
+// Everything needed to decide between racing and defending, in "value" as used by `value_of`
+#[derive(Debug, Default)]
+pub struct BaseTrade {
+    // Frames for our army to get back home
+    pub our_travel_frames: i32,
+    pub home_loss_per_frame: f32,
+    pub home_value_at_risk: i32,
+    pub enemy_loss_per_frame: f32,
+    pub enemy_value_at_risk: i32,
+    // Result of our army coming back and fighting the raiders
+    pub defense: SimResult,
+}
+
+impl BaseTrade {
+    // Both sides keep destroying buildings
+    pub fn race_value(&self) -> i32 {
+        let frames = RACE_FRAMES as f32;
+        ((self.enemy_loss_per_frame * frames) as i32).min(self.enemy_value_at_risk)
+            - ((self.home_loss_per_frame * frames) as i32).min(self.home_value_at_risk)
+    }
+
+    // We lose buildings until the army is back, then fight the raiders
+    pub fn defend_value(&self) -> i32 {
+        self.defense.delta()
+            - ((self.home_loss_per_frame * self.our_travel_frames as f32) as i32)
+                .min(self.home_value_at_risk)
+    }
+
+    pub fn should_race(&self) -> bool {
+        self.race_value() > self.defend_value()
+    }
+}
+
+impl MyModule {
+    // Our army is hitting enemy buildings and `offender` is hitting ours: Decide whether to keep
+    // going (base trade) or to return and defend.
+    pub fn should_base_trade(&self, offender: &SUnit) -> bool {
+        if !offender.get_type().can_move() {
+            // Cannon rush or similar, they're not going anywhere
+            return false;
+        }
+        let Some(home) = self.forward_base().map(|b| b.position()) else {
+            return true;
+        };
+        let Some(raid) = self
+            .skirmishes
+            .skirmishes
+            .iter()
+            .find(|s| s.cluster.units.iter().any(|u| u == offender))
+        else {
+            return false;
+        };
+        let Some(attack) = self
+            .skirmishes
+            .skirmishes
+            .iter()
+            .filter(|s| s.potential_building_loss.enemy_dead > 0)
+            .max_by_key(|s| s.potential_building_loss.enemy_dead)
+        else {
+            return false;
+        };
+        let Some(vanguard) = attack.vanguard.as_ref() else {
+            return false;
+        };
+
+        let buildings_value = |skirmish: &Skirmish, enemy: bool| -> i32 {
+            skirmish
+                .cluster
+                .units
+                .iter()
+                .filter(|u| {
+                    u.get_type().is_building()
+                        && if enemy {
+                            u.player().is_enemy()
+                        } else {
+                            u.player().is_me()
+                        }
+                })
+                .map(|u| self.value_of(u.get_type(), !enemy))
+                .sum()
+        };
+        let walkability = |x, y| {
+            Position::new(x, y).is_valid(&&self.game) && self.game.is_walkable((x / 8, y / 8))
+        };
+        let agents = |skirmish: &Skirmish, filter: &dyn Fn(&SUnit) -> bool| -> Vec<Agent> {
+            skirmish
+                .cluster
+                .units
+                .iter()
+                .filter(|u| filter(u))
+                .map(Agent::from_unit)
+                .collect()
+        };
+        let simulate = |mine: Vec<Agent>, theirs: Vec<Agent>, frames: i32| {
+            let mut sim = Simulator {
+                player_a: Player {
+                    agents: mine,
+                    script: Attacker::new(),
+                },
+                player_b: Player {
+                    agents: theirs,
+                    script: Attacker::new(),
+                },
+                walkability,
+            };
+            sim.simulate_for(frames);
+            sim
+        };
+        let dead_value = |agents: &[Agent], my_unit: bool, buildings_only: bool| -> i32 {
+            agents
+                .iter()
+                .filter(|a| !a.is_alive && (!buildings_only || a.unit_type.is_building()))
+                .map(|a| self.value_of(a.unit_type, my_unit))
+                .sum()
+        };
+
+        // The raiders without our army around
+        let raid_sim = simulate(
+            agents(raid, &|u| u.player().is_me() && !is_attacker(u)),
+            agents(raid, &|u| u.player().is_enemy()),
+            SKIRMISH_SIM_FRAMES,
+        );
+        let attack_sim = simulate(
+            agents(attack, &|u| u.player().is_me() && is_attacker(u)),
+            agents(attack, &|u| u.player().is_enemy()),
+            SKIRMISH_SIM_FRAMES,
+        );
+        // Our army, back home, against the raiders
+        let army: Vec<_> = agents(attack, &|u| u.player().is_me() && is_attacker(u))
+            .into_iter()
+            .chain(agents(raid, &|u| u.player().is_me() && is_attacker(u)))
+            .map(|a| a.with_x(home.x).with_y(home.y))
+            .collect();
+        let defense_sim = simulate(
+            army,
+            agents(raid, &|u| u.player().is_enemy()),
+            DEFENSE_SIM_FRAMES,
+        );
+
+        let trade = BaseTrade {
+            our_travel_frames: self.estimate_frames_to(vanguard, home),
+            home_loss_per_frame: dead_value(&raid_sim.player_a.agents, true, true) as f32
+                / SKIRMISH_SIM_FRAMES as f32,
+            home_value_at_risk: buildings_value(raid, false),
+            enemy_loss_per_frame: dead_value(&attack_sim.player_b.agents, false, true) as f32
+                / SKIRMISH_SIM_FRAMES as f32,
+            enemy_value_at_risk: buildings_value(attack, true),
+            defense: SimResult {
+                my_dead: dead_value(&defense_sim.player_a.agents, true, false),
+                enemy_dead: dead_value(&defense_sim.player_b.agents, false, false),
+            },
+        };
+        let race = trade.should_race();
+        cvis().log(|| {
+            format!(
+                "Base trade: race {} vs defend {} => {}",
+                trade.race_value(),
+                trade.defend_value(),
+                if race { "race" } else { "defend" }
+            )
+        });
+        race
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_defend_when_close_to_home() {
+        let trade = BaseTrade {
+            our_travel_frames: 5 * 24,
+            home_loss_per_frame: 2.0,
+            home_value_at_risk: 2000,
+            enemy_loss_per_frame: 1.0,
+            enemy_value_at_risk: 2000,
+            defense: SimResult {
+                my_dead: 100,
+                enemy_dead: 300,
+            },
+        };
+
+        assert!(!trade.should_race());
+    }
+
+    #[test]
+    fn should_race_if_defense_is_hopeless() {
+        let trade = BaseTrade {
+            our_travel_frames: 30 * 24,
+            home_loss_per_frame: 1.0,
+            home_value_at_risk: 1000,
+            enemy_loss_per_frame: 3.0,
+            enemy_value_at_risk: 3000,
+            defense: SimResult {
+                my_dead: 800,
+                enemy_dead: 100,
+            },
+        };
+
+        assert!(trade.should_race());
+    }
+}
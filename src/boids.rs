@@ -109,12 +109,20 @@ impl MyModule {
 }
 
 pub fn avoid(unit: &SUnit, pos: Position, minimum_distance: f32, weight: f32) -> WeightedPosition {
+    let rnd = unit.id() as f32;
     let unit = pos_to_vec2(unit.position());
     let delta = unit - pos_to_vec2(pos);
     let dist = delta.length();
     if dist > minimum_distance {
         return WeightedPosition::ZERO;
     }
+    if dist == 0.0 {
+        // Right on top of it (ie. a scarab targeting us), any direction will do
+        return WeightedPosition {
+            position: Vec2::from_angle(rnd) * minimum_distance,
+            weight,
+        };
+    }
     // "Push" more if we're closer
     let scale = 1.0 - dist / minimum_distance;
     let position = delta * (minimum_distance - dist) / dist;
@@ -296,6 +304,31 @@ pub fn follow_path(
     result
 }
 
+// Like `follow_path`, but avoiding known enemy weapon ranges
+pub fn follow_safe_path(
+    module: &MyModule,
+    unit: &SUnit,
+    target: Position,
+    weight: f32,
+) -> WeightedPosition {
+    let Some(waypoint) = module.safe_waypoint(unit, target) else {
+        return follow_path(module, unit, target, weight);
+    };
+    if DRAW_FORCE_VECTORS {
+        cvis().draw_line(
+            unit.position().x,
+            unit.position().y,
+            waypoint.x,
+            waypoint.y,
+            rsbwapi::Color::Cyan,
+        );
+    }
+    WeightedPosition {
+        weight,
+        position: pos_to_vec2(waypoint - unit.position()),
+    }
+}
+
 pub fn pos_to_vec2(pos: Position) -> Vec2 {
     Vec2::new(pos.x as f32, pos.y as f32)
 }
@@ -8,6 +8,8 @@ pub struct BuildParam {
 pub enum At {
     Anywhere,
     TilePosition(TilePosition),
+    // Only this exact location, ie. as part of a wall
+    Exactly(TilePosition),
     DefenseChoke,
 }
 
@@ -32,6 +34,11 @@ impl BuildParam {
         self.at = At::TilePosition(at);
         self
     }
+
+    pub fn exactly_at(mut self, at: TilePosition) -> Self {
+        self.at = At::Exactly(at);
+        self
+    }
 }
 
 impl MyModule {
@@ -152,24 +159,10 @@ impl MyModule {
     }
 
     pub fn start_expansion(&mut self) -> Result<(), FailureReason> {
-        // TODO Don't just use the first base found as main base...
-        let base = self
-            .units
-            .my_completed
-            .iter()
-            .filter(|u| u.get_type().is_resource_depot())
-            .next()
-            .ok_or(FailureReason::misc("No base left"))?
-            .position();
-
-        // TODO What if there is no base? We should just pick any spot or give up
-        let location = self
-            .bases
-            .all()
-            .filter(|candidate| candidate.resource_depot.is_none())
-            .min_by_key(|b| self.map.get_path(b.position.center(), base).1)
-            .expect("Expansion location to exist");
-        let pos = location.position;
+        let pos = self
+            .best_expansion()
+            .ok_or(FailureReason::misc("No expansion location left"))?
+            .position;
         self.start_build(BuildParam::build(UnitType::Zerg_Hatchery).at(pos))
     }
 
@@ -179,6 +172,7 @@ impl MyModule {
             "'{:?}' is not a building",
             param.unit_type
         );
+        self.claim_production(ProductionItem::Unit(param.unit_type));
         assert!(
             param.unit_type.price().gas <= 0.max(self.tracker.available_gms.gas)
                 || self.has_pending_ready_or_planned(|ut| ut.is_refinery()),
@@ -190,7 +184,7 @@ impl MyModule {
             param.unit_type,
         ));
         let base = match param.at {
-            At::TilePosition(at) => at,
+            At::TilePosition(at) | At::Exactly(at) => at,
             At::DefenseChoke => self
                 .forward_base()
                 .ok_or(FailureReason::misc("Base not found"))?
@@ -225,6 +219,7 @@ impl MyModule {
                     .ok_or(FailureReason::misc("No builder found"))?;
 
                 self.tracker.reserve_unit(builder);
+                self.assign_production_builder(builder);
                 builder.morph(param.unit_type).ok();
             }
             return Ok(());
@@ -247,6 +242,26 @@ impl MyModule {
                         .map(|b| (b, p))
                 })
                 .ok_or(FailureReason::misc("No vespene geyser found"))??
+        } else if let At::Exactly(at) = param.at {
+            builders
+                .iter()
+                .filter(|b| {
+                    self.game
+                        .can_build_here(&b.unit, at, param.unit_type, false)
+                        .unwrap_or(false)
+                })
+                .min_by_key(|b| dist_fn(b, at))
+                .map(|b| (b, at))
+                .ok_or(FailureReason::misc(format!("Can't build at {}", at)))?
+        } else if let Some(slot) = builders
+            .first()
+            .and_then(|b| self.planned_slot(param.unit_type, &param.at, base, b))
+        {
+            builders
+                .iter()
+                .min_by_key(|b| dist_fn(b, slot))
+                .map(|b| (b, slot))
+                .ok_or(FailureReason::misc("No builder found"))?
         } else {
             let mut candidates = Spiral { center: base }
                 .into_iter()
@@ -261,9 +276,19 @@ impl MyModule {
                 .flatten()
                 .filter(|(b, p)| {
                     let center = p.to_position() + tile_size.to_position() / 2;
-                    self.game
-                        .can_build_here(&b.unit, *p, param.unit_type, false)
-                        .unwrap_or(false)
+                    // Keep the mineral line and lanes of the base layout free
+                    let footprint_free = (0..tile_size.x).all(|dx| {
+                        (0..tile_size.y).all(|dy| {
+                            let t = TilePosition::new(p.x + dx, p.y + dy);
+                            !self.bases.all().any(|sb| sb.layout.reserved.contains(&t))
+                                && !self.is_tile_queued(t)
+                        })
+                    });
+                    footprint_free
+                        && self
+                            .game
+                            .can_build_here(&b.unit, *p, param.unit_type, false)
+                            .unwrap_or(false)
                         && !self
                             .units
                             .all_in_radius(center, 128)
@@ -300,7 +325,9 @@ impl MyModule {
         let build_pos = build_tile_pos.to_position() + tile_size.to_position() / 2;
         // Account for some worker wiggling
         let frames_to_start_build = self.estimate_frames_to(&builder, build_pos) + 24;
-        let future_gms = available_gms + self.estimate_gms(frames_to_start_build, 1);
+        // Income until then is promised to queued items of higher priority first
+        let future_gms = available_gms + self.estimate_gms(frames_to_start_build, 1)
+            - self.promised_income();
         // CVIS.lock().unwrap().log_unit_frame(
         //     &builder,
         //     format!(
@@ -327,6 +354,8 @@ impl MyModule {
             return Err(FailureReason::InsufficientResources);
         }
         self.tracker.reserve_unit(builder);
+        self.assign_production_builder(builder);
+        self.assign_production_location(build_tile_pos);
         if !order_build || builder.position().distance_squared(build_pos) > 128 * 128 {
             CVIS.lock().unwrap().draw_rect(
                 build_pos.x - param.unit_type.dimension_left(),
@@ -335,7 +364,10 @@ impl MyModule {
                 build_pos.y + param.unit_type.dimension_down(),
                 Color::Grey,
             );
-            if builder
+            if builder.position().distance_squared(build_pos) > 320 * 320 {
+                // Expansions might be close to the enemy, don't walk past his defenses
+                self.safe_move_to(builder, build_pos - (0, 7));
+            } else if builder
                 .target_position()
                 .map(|tp| tp.distance_squared(build_pos) > 32 * 32)
                 .unwrap_or(true)
@@ -387,7 +419,7 @@ impl MyModule {
 }
 
 pub struct Spiral<T> {
-    center: T,
+    pub center: T,
 }
 
 pub struct SpiralIterator<T> {
@@ -0,0 +1,370 @@
+use crate::*;
+use num_traits::FromPrimitive;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::fmt::Debug;
+use std::fs::File;
+use std::path::Path;
+
+// A build order loaded from a YAML file, ie.:
+//
+// name: ten_hatch_hydra
+// steps:
+//   - units: { type: Zerg_Drone, count: 10 }
+//   - bases: 2
+//   - buildings: { type: Zerg_Spawning_Pool, count: 1 }
+//   - if:
+//       condition: { enemy_race: Protoss }
+//       then:
+//         - buildings: { type: Zerg_Sunken_Colony, count: 1 }
+//   - upgrade: { type: Grooved_Spines, level: 1 }
+//   - pump: Zerg_Hydralisk
+//   - zvp_wall
+//   - gather_gas: { max_workers: 3 }
+//   - attack: { min_army: 12 }
+#[derive(Debug, Deserialize)]
+pub struct BuildOrder {
+    pub name: String,
+    pub steps: Vec<BuildStep>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum BuildStep {
+    // Same as `ensure_unit_count`
+    Units {
+        #[serde(rename = "type", deserialize_with = "by_name")]
+        unit_type: UnitType,
+        count: usize,
+    },
+    Buildings {
+        #[serde(rename = "type", deserialize_with = "by_name")]
+        unit_type: UnitType,
+        count: usize,
+    },
+    Bases(usize),
+    Upgrade {
+        #[serde(rename = "type", deserialize_with = "by_name")]
+        upgrade: UpgradeType,
+        #[serde(default = "first_level")]
+        level: i32,
+    },
+    Tech(#[serde(deserialize_with = "by_name")] TechType),
+    FreeSupply(i32),
+    Pump(#[serde(deserialize_with = "by_name")] UnitType),
+    // Evolution chambers, hatchery and sunkens at the natural choke
+    ZvpWall,
+    GatherGas {
+        #[serde(default)]
+        required_resources: Option<i32>,
+        #[serde(default)]
+        max_workers: Option<i32>,
+    },
+    Attack {
+        #[serde(default)]
+        min_army: usize,
+        #[serde(default)]
+        min_army_value: i32,
+    },
+    If {
+        condition: BuildCondition,
+        then: Vec<BuildStep>,
+        #[serde(rename = "else", default)]
+        otherwise: Vec<BuildStep>,
+    },
+}
+
+// All given conditions must hold
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BuildCondition {
+    #[serde(default, deserialize_with = "maybe_race")]
+    pub enemy_race: Option<Race>,
+    // At least `count` completed units of `type`
+    #[serde(default)]
+    pub completed: Option<UnitCount>,
+    // Less than `count` pending or completed units of `type`
+    #[serde(default)]
+    pub less_than: Option<UnitCount>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct UnitCount {
+    #[serde(rename = "type", deserialize_with = "by_name")]
+    pub unit_type: UnitType,
+    pub count: usize,
+}
+
+fn first_level() -> i32 {
+    1
+}
+
+// BWAPI types by their enum name, ie. "Zerg_Drone" or "Metabolic_Boost"
+fn by_name<'de, D: Deserializer<'de>, T: FromPrimitive + Debug>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    (0..256)
+        .filter_map(T::from_i32)
+        .find(|t| format!("{:?}", t) == name)
+        .ok_or_else(|| D::Error::custom(format!("Unknown type '{name}'")))
+}
+
+fn maybe_race<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Race>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match name.as_str() {
+        "Zerg" => Ok(Some(Race::Zerg)),
+        "Terran" => Ok(Some(Race::Terran)),
+        "Protoss" => Ok(Some(Race::Protoss)),
+        _ => Err(D::Error::custom(format!("Unknown race '{name}'"))),
+    }
+}
+
+impl BuildOrder {
+    pub fn parse(yaml: &str) -> anyhow::Result<Self> {
+        serde_yaml::from_str(yaml).map_err(anyhow::Error::new)
+    }
+}
+
+// Build orders in bwapi-data/read/build_orders, broken files are skipped
+pub fn load_build_orders() -> Vec<BuildOrder> {
+    let Ok(dir) = std::fs::read_dir(Path::new("bwapi-data").join("read").join("build_orders"))
+    else {
+        return vec![];
+    };
+    dir.filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| ext == "yaml" || ext == "yml")
+                .unwrap_or(false)
+        })
+        .filter_map(|path| {
+            File::open(&path)
+                .map_err(anyhow::Error::new)
+                .and_then(|file| serde_yaml::from_reader(file).map_err(anyhow::Error::new))
+                .map_err(|e| error!("Failed to load build order {:?}: {}", path, e))
+                .ok()
+        })
+        .collect()
+}
+
+impl MyModule {
+    // Race of the enemy, as soon as we know it
+    pub fn enemy_race(&self) -> Option<Race> {
+        self.game
+            .enemy()
+            .map(|e| e.get_race())
+            .filter(|r| matches!(r, Race::Zerg | Race::Terran | Race::Protoss))
+            .or_else(|| self.units.enemy.first().map(|u| u.get_type().get_race()))
+    }
+
+    fn build_condition_holds(&self, condition: &BuildCondition) -> bool {
+        condition
+            .enemy_race
+            .map(|race| self.enemy_race() == Some(race))
+            .unwrap_or(true)
+            && condition
+                .completed
+                .as_ref()
+                .map(|c| self.count_completed(|ut| ut == c.unit_type) >= c.count)
+                .unwrap_or(true)
+            && condition
+                .less_than
+                .as_ref()
+                .map(|c| self.count_pending_or_ready(|ut| ut == c.unit_type) < c.count)
+                .unwrap_or(true)
+    }
+
+    fn execute_build_steps(
+        &mut self,
+        steps: &[BuildStep],
+        gather_params: &mut GatherParams,
+        attack_params: &mut AttackParams,
+    ) {
+        for step in steps {
+            let result = match step {
+                BuildStep::Units { unit_type, count } => self.ensure_unit_count(*unit_type, *count),
+                BuildStep::Buildings { unit_type, count } => {
+                    self.ensure_building_count(*unit_type, *count)
+                }
+                BuildStep::Bases(count) => self.ensure_base_count(*count),
+                BuildStep::Upgrade { upgrade, level } => self.ensure_upgrade(*upgrade, *level),
+                BuildStep::Tech(tech) => self.ensure_tech(*tech),
+                BuildStep::FreeSupply(amount) => {
+                    self.ensure_free_supply(*amount);
+                    Ok(())
+                }
+                BuildStep::Pump(unit_type) => self.pump(*unit_type),
+                BuildStep::ZvpWall => self.ensure_zvp_wall(),
+                BuildStep::GatherGas {
+                    required_resources,
+                    max_workers,
+                } => {
+                    let default = GatherParams::default();
+                    *gather_params = GatherParams {
+                        required_resources: required_resources
+                            .unwrap_or(default.required_resources),
+                        max_workers: max_workers.unwrap_or(default.max_workers),
+                    };
+                    Ok(())
+                }
+                BuildStep::Attack {
+                    min_army,
+                    min_army_value,
+                } => {
+                    attack_params.min_army = *min_army;
+                    attack_params.min_army_value = *min_army_value;
+                    Ok(())
+                }
+                BuildStep::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let steps = if self.build_condition_holds(condition) {
+                        then
+                    } else {
+                        otherwise
+                    };
+                    self.execute_build_steps(steps, gather_params, attack_params);
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                cvis().log(|| format!("Build step {:?} failed: {:?}", step, err));
+            }
+        }
+    }
+
+    // Steps are processed in order every frame, just like the hand written strategies
+    pub fn execute_build_order(&mut self, build_order: &BuildOrder) -> anyhow::Result<()> {
+        let mut gather_params = GatherParams::default();
+        let mut attack_params = AttackParams::default();
+        self.execute_build_steps(&build_order.steps, &mut gather_params, &mut attack_params);
+        self.ensure_gathering_gas(gather_params);
+        self.perform_attacking(attack_params)?;
+        self.perform_scouting(ScoutParams::default()).ok();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_build_order() {
+        let build_order = BuildOrder::parse(
+            r#"
+name: test_order
+steps:
+  - units: { type: Zerg_Drone, count: 9 }
+  - buildings: { type: Zerg_Spawning_Pool, count: 1 }
+  - bases: 2
+  - upgrade: { type: Metabolic_Boost }
+  - free_supply: 4
+  - pump: Zerg_Zergling
+  - zvp_wall
+  - attack: { min_army: 12 }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(build_order.name, "test_order");
+        assert_eq!(
+            build_order.steps,
+            vec![
+                BuildStep::Units {
+                    unit_type: UnitType::Zerg_Drone,
+                    count: 9
+                },
+                BuildStep::Buildings {
+                    unit_type: UnitType::Zerg_Spawning_Pool,
+                    count: 1
+                },
+                BuildStep::Bases(2),
+                BuildStep::Upgrade {
+                    upgrade: UpgradeType::Metabolic_Boost,
+                    level: 1
+                },
+                BuildStep::FreeSupply(4),
+                BuildStep::Pump(UnitType::Zerg_Zergling),
+                BuildStep::ZvpWall,
+                BuildStep::Attack {
+                    min_army: 12,
+                    min_army_value: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_conditional_steps() {
+        let build_order = BuildOrder::parse(
+            r#"
+name: test_order
+steps:
+  - if:
+      condition: { enemy_race: Protoss }
+      then:
+        - buildings: { type: Zerg_Sunken_Colony, count: 2 }
+      else:
+        - units: { type: Zerg_Zergling, count: 6 }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            build_order.steps,
+            vec![BuildStep::If {
+                condition: BuildCondition {
+                    enemy_race: Some(Race::Protoss),
+                    ..Default::default()
+                },
+                then: vec![BuildStep::Buildings {
+                    unit_type: UnitType::Zerg_Sunken_Colony,
+                    count: 2
+                }],
+                otherwise: vec![BuildStep::Units {
+                    unit_type: UnitType::Zerg_Zergling,
+                    count: 6
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_unit_type() {
+        assert!(BuildOrder::parse(
+            r#"
+name: test_order
+steps:
+  - units: { type: Zerg_Battlecruiser, count: 1 }
+"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn should_reject_misspelled_condition() {
+        assert!(BuildOrder::parse(
+            r#"
+name: test_order
+steps:
+  - if:
+      condition: { enemy_rcae: Protoss }
+      then:
+        - zvp_wall
+"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn should_parse_sample_build_order() {
+        let build_order =
+            BuildOrder::parse(include_str!("../dist/build_orders/ten_hatch_hydra.yaml")).unwrap();
+
+        assert_eq!(build_order.name, "ten_hatch_hydra");
+    }
+}
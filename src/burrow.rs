@@ -0,0 +1,160 @@
+use crate::*;
+use ahash::AHashMap;
+
+// Burrow to regenerate below this fraction of hit points...
+const REGEN_BURROW_HEALTH_PERCENT: i32 = 35;
+// ...and come out again above this
+const REGEN_UNBURROW_HEALTH_PERCENT: i32 = 90;
+// Expansions watched by burrowed lings
+const MAX_WATCHTOWERS: usize = 4;
+
+#[derive(Default)]
+pub struct Burrows {
+    // Lings sitting at expansion sites
+    watchtowers: AHashMap<TilePosition, SUnit>,
+    // Units burrowed to regenerate
+    regenerating: Vec<SUnit>,
+}
+
+impl MyModule {
+    // Burrowed, nobody can see us and there is enough time to regenerate
+    fn safe_to_burrow(&self, unit: &SUnit) -> bool {
+        !self.detected_by_enemy(unit)
+            && self
+                .units
+                .all_in_range(unit, 10 * 32)
+                .all(|e| !e.player().is_enemy() || !e.get_type().is_detector())
+    }
+
+    fn perform_regen_burrow(&mut self) {
+        let me = self.game.self_().unwrap();
+        self.burrows.regenerating.retain(|u| u.exists());
+        let candidates: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| {
+                u.get_type().is_burrowable()
+                    && u.get_type() != UnitType::Zerg_Lurker
+                    && !u.get_type().is_worker()
+                    && !u.burrowed()
+                    && !self.burrows.regenerating.contains(u)
+                    && u.hit_points() * 100
+                        < u.get_type().max_hit_points() * REGEN_BURROW_HEALTH_PERCENT
+            })
+            .cloned()
+            .collect();
+        if me.has_researched(TechType::Burrowing) {
+            for unit in candidates {
+                if self.safe_to_burrow(&unit) {
+                    cvis().log_unit_frame(&unit, || "Burrowing to regenerate");
+                    self.tracker.reserve_unit(&unit);
+                    unit.burrow().ok();
+                    self.burrows.regenerating.push(unit);
+                }
+            }
+        }
+        for unit in self.burrows.regenerating.clone() {
+            if !self.tracker.available_units.contains(&unit) {
+                continue;
+            }
+            let healed = unit.hit_points() * 100
+                >= unit.get_type().max_hit_points() * REGEN_UNBURROW_HEALTH_PERCENT;
+            if healed || !self.safe_to_burrow(&unit) {
+                // Back to the squad
+                cvis().log_unit_frame(&unit, || "Done regenerating");
+                unit.unburrow().ok();
+                self.burrows.regenerating.retain(|u| *u != unit);
+            } else {
+                self.tracker.reserve_unit(&unit);
+            }
+        }
+    }
+
+    fn perform_watchtowers(&mut self) {
+        let frame = self.game.get_frame_count();
+        // Someone took the base, if it was the enemy our ling did its job (and will be found soon)
+        let taken: Vec<_> = self
+            .bases
+            .all()
+            .filter(|b| self.burrows.watchtowers.contains_key(&b.position))
+            .filter_map(|b| Some((b.position, b.player.as_ref()?.is_enemy())))
+            .collect();
+        for (base, by_enemy) in taken {
+            if let Some(ling) = self.burrows.watchtowers.remove(&base) {
+                if by_enemy {
+                    info!("{frame}: Enemy expanded at {base:?}");
+                    cvis().log(|| format!("Watchtower at {base:?}: Enemy took the base"));
+                }
+                if ling.exists() {
+                    ling.unburrow().ok();
+                }
+            }
+        }
+        let available = &self.tracker.available_units;
+        self.burrows
+            .watchtowers
+            .retain(|_, ling| ling.exists() && available.contains(ling));
+
+        let Some(home) = self.forward_base().map(|b| b.position()) else {
+            return;
+        };
+        // Expansions closest to us are most likely to be taken by us, watch the others
+        let mut sites: Vec<_> = self
+            .bases
+            .all()
+            .filter(|b| b.player.is_none())
+            .map(|b| b.position)
+            .collect();
+        sites.sort_by_key(|b| -(b.center().distance(home) as i32));
+        sites.truncate(MAX_WATCHTOWERS);
+        self.burrows
+            .watchtowers
+            .retain(|base, _| sites.contains(base));
+
+        for site in sites {
+            let ling = match self.burrows.watchtowers.get(&site) {
+                Some(ling) => ling.clone(),
+                None => {
+                    let Some(ling) = self
+                        .tracker
+                        .available_units
+                        .iter()
+                        .filter(|u| u.get_type() == UnitType::Zerg_Zergling && !u.burrowed())
+                        .min_by_key(|u| u.position().distance(site.center()) as i32)
+                        .cloned()
+                    else {
+                        return;
+                    };
+                    self.burrows.watchtowers.insert(site, ling.clone());
+                    ling
+                }
+            };
+            self.tracker.reserve_unit(&ling);
+            // Not in the middle of the spot, buildings would be blocked
+            let spot = site.center() + Position::new(0, -64);
+            if ling.position().distance(spot) > 16.0 {
+                if ling.burrowed() {
+                    ling.unburrow().ok();
+                } else {
+                    self.safe_move_to(&ling, spot);
+                }
+            } else if !ling.burrowed() {
+                ling.burrow().ok();
+            }
+        }
+    }
+
+    // Burrow injured units to regenerate and keep single lings as tripwires at expansions
+    pub fn perform_burrow(&mut self) {
+        self.perform_regen_burrow();
+        if self
+            .game
+            .self_()
+            .unwrap()
+            .has_researched(TechType::Burrowing)
+        {
+            self.perform_watchtowers();
+        }
+    }
+}
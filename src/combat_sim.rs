@@ -191,6 +191,10 @@ impl Agent {
             cooldown: unit.cooldown(),
             detected: unit.detected() || !unit.cloaked(),
             burrowed: unit.burrowed(),
+            // Units using Burrow need the research, burrowed units evidently have it
+            can_unburrow: base.can_unburrow
+                || unit.burrowed()
+                || unit_type.is_burrowable() && player.has_researched(TechType::Burrowing),
             stasis_timer: unit.stasis_timer(),
             sleep_timer: base.sleep_timer.max(
                 if !unit.powered() || (unit.exists() && (unit.gathering() || unit.constructing())) {
@@ -329,6 +333,7 @@ impl Agent {
             ),
             detected: !unit_type.has_permanent_cloak(),
             burrowed_attacker: unit_type == UnitType::Zerg_Lurker,
+            can_unburrow: unit_type == UnitType::Zerg_Lurker,
             base_speed: unit_type.top_speed() as f32,
             speed_factor: 1.0,
             protoss_scout: unit_type == UnitType::Protoss_Scout,
@@ -809,6 +814,34 @@ impl Script for Retreater {
     }
 }
 
+// Runs for the goal without fighting back, ie. a run-by past static defense
+#[derive(Clone, Copy, Debug)]
+pub struct Runner {
+    pub goal: (i32, i32),
+}
+
+impl Script for Runner {
+    fn simulate(
+        &mut self,
+        agent_index: usize,
+        allies: &mut [Agent],
+        _enemies: &mut [Agent],
+    ) -> bool {
+        let agent = &mut allies[agent_index];
+        if agent.burrowed {
+            return agent.unburrow();
+        }
+        let dx = (self.goal.0 - agent.x) as f32;
+        let dy = (self.goal.1 - agent.y) as f32;
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance < 1.0 {
+            return false;
+        }
+        move_toward(agent, self.goal, distance, 0);
+        true
+    }
+}
+
 #[derive(Clone, Copy, Default, Debug)]
 pub struct Attacker {}
 
@@ -1383,6 +1416,39 @@ mod test {
         assert_eq!(agent.vy, 0);
     }
 
+    #[test]
+    fn lings_should_run_past_cannon() {
+        let lings: Vec<_> = (0..6)
+            .map(|i| {
+                Agent::from(UnitType::Zerg_Zergling)
+                    .with_x(-400)
+                    .with_y(i * 16)
+            })
+            .collect();
+        let mut simulator = Simulator {
+            player_a: Player {
+                agents: lings,
+                script: Runner { goal: (400, 0) },
+            },
+            player_b: Player {
+                agents: vec![Agent::from(UnitType::Protoss_Photon_Cannon).with_y(160)],
+                script: Attacker::new(),
+            },
+            walkability: |x, y| true,
+        };
+
+        simulator.simulate_for(24 * 12);
+
+        let survivors: Vec<_> = simulator
+            .player_a
+            .agents
+            .iter()
+            .filter(|a| a.is_alive)
+            .collect();
+        assert!(survivors.len() >= 3);
+        assert!(survivors.iter().all(|a| a.x >= 390));
+    }
+
     #[test]
     fn archon_splash_should_not_affect_own_units() {
         let mut simulator = Simulator {
@@ -1941,4 +2007,53 @@ mod test {
             4
         );
     }
+
+    #[test]
+    fn burrowed_ling_unburrows_to_attack() {
+        let ling = Agent {
+            burrowed: true,
+            can_unburrow: true,
+            ..Agent::from(UnitType::Zerg_Zergling).with_x(100).with_y(100)
+        };
+        let marine = Agent::from(UnitType::Terran_Marine).with_x(120).with_y(100);
+        let mut simulator = Simulator {
+            player_a: Player {
+                agents: vec![ling],
+                script: Attacker::new(),
+            },
+            player_b: Player {
+                agents: vec![marine],
+                script: Attacker::new(),
+            },
+            walkability: |_, _| true,
+        };
+
+        simulator.simulate_for(1);
+
+        assert!(!simulator.player_a.agents[0].burrowed);
+    }
+
+    #[test]
+    fn lurker_burrows_to_attack() {
+        let lurker = Agent::from(UnitType::Zerg_Lurker).with_x(100).with_y(100);
+        let marine = Agent::from(UnitType::Terran_Marine).with_x(250).with_y(100);
+        let mut simulator = Simulator {
+            player_a: Player {
+                agents: vec![lurker],
+                script: Attacker::new(),
+            },
+            player_b: Player {
+                agents: vec![marine],
+                script: Attacker::new(),
+            },
+            walkability: |_, _| true,
+        };
+
+        simulator.simulate_for(1);
+        assert!(simulator.player_a.agents[0].burrowed);
+
+        simulator.simulate_for(5 * 24);
+        assert!(simulator.player_a.agents[0].is_alive);
+        assert!(!simulator.player_b.agents[0].is_alive);
+    }
 }
@@ -7,6 +7,9 @@ pub const DRAW_FORCE_VECTORS: bool = false;
 // Draw scouting destination
 pub const DRAW_SCOUT_TARGET: bool = false;
 
+// Draw threat and control values at enemy positions
+pub const DRAW_INFLUENCE_MAPS: bool = false;
+
 // Draw gathering targets
 pub const DRAW_GATHERING_TARGET: bool = true;
 
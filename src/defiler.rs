@@ -0,0 +1,161 @@
+use crate::*;
+
+// Distance to keep behind the vanguard
+const DEFILER_DISTANCE_TO_VANGUARD: i32 = 4 * 32;
+// Minimum number of our melee units that need to be covered by a Dark Swarm
+const MIN_SWARM_UNITS: usize = 3;
+// Minimum value of enemy units hit to cast Plague
+const MIN_PLAGUE_VALUE: i32 = 400;
+const CAST_RANGE: i32 = 9 * 32;
+const SWARM_RADIUS: i32 = 3 * 32;
+const PLAGUE_RADIUS: i32 = 2 * 32;
+
+fn is_melee(unit: &SUnit) -> bool {
+    let weapon = unit.get_ground_weapon();
+    weapon.weapon_type != WeaponType::None && weapon.max_range <= 32
+}
+
+// Position to cast an area spell on, which maximizes the value of `units` hit. Only positions
+// of units themselves are considered.
+pub fn best_spell_position(
+    units: &[&SUnit],
+    radius: i32,
+    value: impl Fn(&SUnit) -> i32,
+) -> Option<(Position, i32)> {
+    units
+        .iter()
+        .map(|u| {
+            (
+                u.position(),
+                units
+                    .iter()
+                    .filter(|o| o.position().distance(u.position()) as i32 <= radius)
+                    .map(|o| value(o))
+                    .sum::<i32>(),
+            )
+        })
+        .max_by_key(|(_, v)| *v)
+}
+
+impl MyModule {
+    fn cast_or_approach(&self, caster: &SUnit, tech: TechType, target: Position) {
+        if caster.position().distance(target) as i32 <= CAST_RANGE {
+            cvis().log_unit_frame(caster, || format!("Casting {:?}", tech));
+            caster.use_tech_position(tech, target).ok();
+        } else {
+            self.safe_move_to(caster, target);
+        }
+    }
+
+    fn dark_swarm_target(&self, defiler: &SUnit) -> Option<Position> {
+        let swarms: Vec<_> = self
+            .units
+            .all()
+            .filter(|u| u.get_type() == UnitType::Spell_Dark_Swarm)
+            .map(|u| u.position())
+            .collect();
+        // Melee units fighting ranged units or static defense profit from swarm
+        let endangered: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| {
+                is_melee(u)
+                    && !u.under_dark_swarm()
+                    && u.position().distance(defiler.position()) < 12.0 * 32.0
+                    && self.units.all_in_range(*u, 300).any(|e| {
+                        e.player().is_enemy()
+                            && e.completed()
+                            && (e.weapon_against(u).max_range > 32 || !e.get_type().can_move())
+                            && e.is_in_weapon_range(u)
+                    })
+            })
+            .collect();
+        let (position, covered) = best_spell_position(&endangered, SWARM_RADIUS, |_| 1)?;
+        if covered < MIN_SWARM_UNITS as i32
+            || swarms
+                .iter()
+                .any(|s| s.distance(position) < SWARM_RADIUS as f64)
+        {
+            return None;
+        }
+        Some(position)
+    }
+
+    fn plague_target(&self, defiler: &SUnit) -> Option<Position> {
+        let enemies: Vec<_> = self
+            .units
+            .enemy
+            .iter()
+            .filter(|e| {
+                e.exists()
+                    && !e.plagued()
+                    && !e.get_type().is_building()
+                    && e.position().distance(defiler.position()) < 12.0 * 32.0
+            })
+            .collect();
+        let (position, value) = best_spell_position(&enemies, PLAGUE_RADIUS, |e| {
+            self.value_of(e.get_type(), false) * e.hit_points().min(300) / 300
+        })?;
+        // Don't hit our own units too hard
+        let friendly_value: i32 = self
+            .units
+            .all_in_radius(position, PLAGUE_RADIUS)
+            .filter(|u| u.player().is_me())
+            .map(|u| self.value_of(u.get_type(), true))
+            .sum();
+        if value - friendly_value < MIN_PLAGUE_VALUE {
+            return None;
+        }
+        Some(position)
+    }
+
+    // Cast Dark Swarm and Plague, refill energy by consuming lings and stay behind the vanguard
+    pub fn perform_defilers(&mut self) {
+        let defilers: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| u.get_type() == UnitType::Zerg_Defiler)
+            .cloned()
+            .collect();
+        let me = self.game.self_().unwrap();
+        for defiler in defilers.iter() {
+            self.tracker.reserve_unit(defiler);
+            let energy = defiler.energy();
+            if energy >= TechType::Dark_Swarm.energy_cost() {
+                if let Some(target) = self.dark_swarm_target(defiler) {
+                    self.cast_or_approach(defiler, TechType::Dark_Swarm, target);
+                    continue;
+                }
+            }
+            if me.has_researched(TechType::Plague) && energy >= TechType::Plague.energy_cost() {
+                if let Some(target) = self.plague_target(defiler) {
+                    self.cast_or_approach(defiler, TechType::Plague, target);
+                    continue;
+                }
+            }
+            if me.has_researched(TechType::Consume) && energy < TechType::Plague.energy_cost() {
+                let snack = self
+                    .tracker
+                    .available_units
+                    .iter()
+                    .filter(|u| {
+                        u.get_type() == UnitType::Zerg_Zergling
+                            && u.position().distance(defiler.position()) < 8.0 * 32.0
+                    })
+                    .min_by_key(|u| u.position().distance(defiler.position()) as i32)
+                    .cloned();
+                if let Some(snack) = snack {
+                    self.tracker.reserve_unit(&snack);
+                    cvis().log_unit_frame(defiler, || format!("Consuming {}", snack.id()));
+                    defiler.use_tech_unit(TechType::Consume, &snack).ok();
+                    // Keep the ling still, so it won't run off
+                    snack.stop().ok();
+                    continue;
+                }
+            }
+            self.stay_behind_vanguard(defiler, DEFILER_DISTANCE_TO_VANGUARD);
+        }
+    }
+}
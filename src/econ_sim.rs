@@ -0,0 +1,597 @@
+use crate::*;
+
+// Frames for a hatchery to spawn another larva
+const LARVA_FRAMES: i32 = 342;
+const MAX_LARVA: i32 = 3;
+const MINERAL_PATCHES_PER_BASE: i32 = 8;
+// Average distance of patches and geysers to the hatchery, from edge to edge
+const RESOURCE_DISTANCE: f64 = 3.0 * 32.0;
+const WORKERS_PER_GEYSER: i32 = WORKERS_PER_REFINERY as i32;
+// Frames for a drone to get to the build location
+const BUILDER_TRAVEL_FRAMES: i32 = 4 * 24;
+const EXPANSION_TRAVEL_FRAMES: i32 = 15 * 24;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimItem {
+    Unit(UnitType),
+    Upgrade(UpgradeType, i32),
+    Tech(TechType),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SimEvent {
+    pub item: SimItem,
+    pub start: i32,
+    pub complete: i32,
+}
+
+#[derive(Clone, Debug)]
+struct SimUnit {
+    unit_type: UnitType,
+    // Lair morphing from a Hatchery still counts as Hatchery
+    morphed_from: UnitType,
+    complete: i32,
+    // Researching, upgrading or morphing
+    busy_until: i32,
+    // Resource depot placed at its own mineral line
+    base: bool,
+}
+
+struct Hatch {
+    larva: i32,
+    next_larva: i32,
+}
+
+// Forward simulation of our macro: Larva, income with saturation, supply, build times and
+// requirements. The build order steps are evaluated every frame, the same way the bot does.
+pub struct EconSim {
+    pub frame: i32,
+    pub minerals: f64,
+    pub gas: f64,
+    pub enemy_race: Option<Race>,
+    pub events: Vec<SimEvent>,
+    units: Vec<SimUnit>,
+    hatches: Vec<Hatch>,
+    // Upgrade, level, frame of completion
+    upgrades: Vec<(UpgradeType, i32, i32)>,
+    techs: Vec<(TechType, i32)>,
+    gather_params: GatherParams,
+    // Gas of everything requested this frame, drives the drones on gas like the live controller
+    queued_gas: i32,
+    gas_workers: i32,
+    last_gas_change: i32,
+    // What is left this frame, after reservations of earlier steps
+    available: Gms,
+}
+
+impl Default for EconSim {
+    fn default() -> Self {
+        let unit = |unit_type| SimUnit {
+            unit_type,
+            morphed_from: UnitType::None,
+            complete: 0,
+            busy_until: 0,
+            base: unit_type.is_resource_depot(),
+        };
+        let mut units = vec![unit(UnitType::Zerg_Hatchery), unit(UnitType::Zerg_Overlord)];
+        units.extend((0..4).map(|_| unit(UnitType::Zerg_Drone)));
+        Self {
+            frame: 0,
+            minerals: 50.0,
+            gas: 0.0,
+            enemy_race: None,
+            events: vec![],
+            units,
+            hatches: vec![Hatch {
+                larva: MAX_LARVA,
+                next_larva: LARVA_FRAMES,
+            }],
+            upgrades: vec![],
+            techs: vec![],
+            gather_params: GatherParams::default(),
+            queued_gas: 0,
+            gas_workers: 0,
+            last_gas_change: 0,
+            available: Gms::default(),
+        }
+    }
+}
+
+impl EconSim {
+    pub fn run(&mut self, steps: &[BuildStep], frames: i32) -> &[SimEvent] {
+        for _ in 0..frames {
+            self.step(steps);
+        }
+        &self.events
+    }
+
+    pub fn step(&mut self, steps: &[BuildStep]) {
+        let frame = self.frame;
+        for _ in self
+            .units
+            .iter()
+            .filter(|u| u.unit_type == UnitType::Zerg_Hatchery && u.complete == frame && frame > 0)
+        {
+            self.hatches.push(Hatch {
+                larva: 1,
+                next_larva: frame + LARVA_FRAMES,
+            });
+        }
+        for hatch in self.hatches.iter_mut() {
+            if hatch.larva >= MAX_LARVA {
+                hatch.next_larva = frame + LARVA_FRAMES;
+            } else if frame >= hatch.next_larva {
+                hatch.larva += 1;
+                hatch.next_larva = frame + LARVA_FRAMES;
+            }
+        }
+        self.gather();
+
+        self.available = Gms {
+            minerals: self.minerals as i32,
+            gas: self.gas as i32,
+            supply: self.supply_total() - self.supply_used(),
+        };
+        self.gather_params = GatherParams::default();
+        self.queued_gas = 0;
+        self.execute(steps);
+        self.frame += 1;
+    }
+
+    fn completed(&self, unit_type: UnitType) -> usize {
+        self.units
+            .iter()
+            .filter(|u| {
+                u.complete <= self.frame && u.unit_type == unit_type || u.morphed_from == unit_type
+            })
+            .count()
+    }
+
+    fn pending_or_ready(&self, check: impl Fn(UnitType) -> bool) -> usize {
+        self.units.iter().filter(|u| check(u.unit_type)).count()
+    }
+
+    fn supply_total(&self) -> i32 {
+        self.units
+            .iter()
+            .filter(|u| u.complete <= self.frame)
+            .map(|u| u.unit_type.supply_provided())
+            .sum::<i32>()
+            .min(400)
+    }
+
+    fn supply_used(&self) -> i32 {
+        self.units
+            .iter()
+            .map(|u| u.unit_type.supply_required())
+            .sum()
+    }
+
+    fn gather(&mut self) {
+        let drones = self.completed(UnitType::Zerg_Drone) as i32;
+        let geysers = self.completed(UnitType::Zerg_Extractor) as i32;
+        let GatherParams {
+            required_resources,
+            max_workers,
+        } = self.gather_params;
+        let per_worker = ResourceIncome {
+            kind: ResourceKind::Gas,
+            distance: RESOURCE_DISTANCE,
+            workers: 1,
+            remaining: 1,
+        }
+        .income(GAS_HORIZON_FRAMES);
+        let shortfall = self.queued_gas + required_resources.max(0) - self.gas as i32;
+        let max = (geysers * WORKERS_PER_GEYSER).min(max_workers).max(0) as usize;
+        let wanted = gas_workers_wanted(
+            self.gas_workers as usize,
+            gas_workers_needed(shortfall, per_worker),
+            max,
+        ) as i32;
+        if wanted != self.gas_workers
+            && (self.gas_workers as usize > max
+                || self.frame - self.last_gas_change >= MIN_SWITCH_FRAMES)
+        {
+            self.gas_workers = wanted;
+            self.last_gas_change = self.frame;
+        }
+        let gas_workers = self.gas_workers.min(drones);
+        let mineral_workers = drones - gas_workers;
+        let patches = self
+            .units
+            .iter()
+            .filter(|u| u.base && u.complete <= self.frame)
+            .count() as i32
+            * MINERAL_PATCHES_PER_BASE;
+        // Workers spread evenly, surplus workers at the last base are idle
+        let rate = |kind, workers| {
+            ResourceIncome {
+                kind,
+                distance: RESOURCE_DISTANCE,
+                workers,
+                remaining: 1,
+            }
+            .rate()
+        };
+        if patches > 0 {
+            let spread = (mineral_workers / patches).min(3);
+            let extra = (mineral_workers - spread * patches).min(patches);
+            self.minerals += extra as f64 * rate(ResourceKind::Minerals, (spread + 1).min(3))
+                + (patches - extra) as f64 * rate(ResourceKind::Minerals, spread);
+        }
+        self.gas += (0..geysers)
+            .map(|i| {
+                rate(
+                    ResourceKind::Gas,
+                    (gas_workers - i * WORKERS_PER_GEYSER).clamp(0, WORKERS_PER_GEYSER),
+                )
+            })
+            .sum::<f64>();
+    }
+
+    fn condition_holds(&self, condition: &BuildCondition) -> bool {
+        condition
+            .enemy_race
+            .map(|race| self.enemy_race == Some(race))
+            .unwrap_or(true)
+            && condition
+                .completed
+                .as_ref()
+                .map(|c| self.completed(c.unit_type) >= c.count)
+                .unwrap_or(true)
+            && condition
+                .less_than
+                .as_ref()
+                .map(|c| self.pending_or_ready(|ut| ut == c.unit_type) < c.count)
+                .unwrap_or(true)
+    }
+
+    fn execute(&mut self, steps: &[BuildStep]) {
+        for step in steps {
+            match step {
+                BuildStep::Units { unit_type, count } => {
+                    let units_per_egg = 1 + unit_type.is_two_units_in_one_egg() as usize;
+                    let missing =
+                        count.saturating_sub(self.pending_or_ready(|ut| ut == *unit_type));
+                    for _ in 0..(units_per_egg / 2 + missing) / units_per_egg {
+                        self.start(*unit_type, false);
+                    }
+                }
+                BuildStep::Buildings { unit_type, count } => {
+                    let missing = count
+                        .saturating_sub(self.pending_or_ready(|ut| ut.is_successor_of(*unit_type)));
+                    for _ in 0..missing {
+                        self.start(*unit_type, false);
+                    }
+                }
+                BuildStep::Bases(count) => {
+                    let missing =
+                        count.saturating_sub(self.units.iter().filter(|u| u.base).count());
+                    for _ in 0..missing {
+                        self.start(UnitType::Zerg_Hatchery, true);
+                    }
+                }
+                BuildStep::Upgrade { upgrade, level } => self.start_upgrade(*upgrade, *level),
+                BuildStep::Tech(tech) => self.start_tech(*tech),
+                BuildStep::FreeSupply(amount) => {
+                    let pending_supply: i32 = self
+                        .units
+                        .iter()
+                        .map(|u| u.unit_type.supply_provided() - u.unit_type.supply_required())
+                        .sum();
+                    if pending_supply / 2 < *amount {
+                        self.start(UnitType::Zerg_Overlord, false);
+                    }
+                }
+                BuildStep::Pump(unit_type) => {
+                    let builder = unit_type.what_builds().0;
+                    let trainers = if builder == UnitType::Zerg_Larva {
+                        self.hatches.iter().map(|h| h.larva as usize).sum()
+                    } else {
+                        self.completed(builder)
+                    };
+                    for _ in 0..trainers {
+                        self.start(*unit_type, false);
+                    }
+                }
+                BuildStep::GatherGas {
+                    required_resources,
+                    max_workers,
+                } => {
+                    let default = GatherParams::default();
+                    self.gather_params = GatherParams {
+                        required_resources: required_resources
+                            .unwrap_or(default.required_resources),
+                        max_workers: max_workers.unwrap_or(default.max_workers),
+                    };
+                }
+                BuildStep::Attack { .. } => (),
+                BuildStep::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    if self.condition_holds(condition) {
+                        self.execute(then);
+                    } else {
+                        self.execute(otherwise);
+                    }
+                }
+            }
+        }
+    }
+
+    fn has_requirements_for(&self, unit_type: UnitType) -> bool {
+        let builder = unit_type.what_builds().0;
+        for it in unit_type.required_units() {
+            if it.0 != builder
+                && it.0 != UnitType::Zerg_Larva
+                && self
+                    .units
+                    .iter()
+                    .filter(|u| {
+                        u.complete <= self.frame && u.unit_type.is_successor_of(it.0)
+                            || u.morphed_from.is_successor_of(it.0)
+                    })
+                    .count()
+                    < it.1 as usize
+            {
+                return false;
+            }
+        }
+        unit_type.required_tech() == TechType::None
+            || self
+                .techs
+                .iter()
+                .any(|&(t, complete)| t == unit_type.required_tech() && complete <= self.frame)
+    }
+
+    // Money is reserved even if we can't start yet, later steps have to wait
+    fn start(&mut self, unit_type: UnitType, base: bool) {
+        if !self.has_requirements_for(unit_type) {
+            return;
+        }
+        let units_per_egg = 1 + unit_type.is_two_units_in_one_egg() as i32;
+        let mut price = unit_type.price();
+        price.supply *= units_per_egg;
+        self.queued_gas += price.gas;
+        if !self.available.checked_sub(price) {
+            return;
+        }
+        let builder_type = unit_type.what_builds().0;
+        let frame = self.frame;
+        let (morphed_from, start, base) = if builder_type == UnitType::Zerg_Larva {
+            let Some(hatch) = self.hatches.iter_mut().find(|h| h.larva > 0) else {
+                return;
+            };
+            if hatch.larva == MAX_LARVA {
+                hatch.next_larva = frame + LARVA_FRAMES;
+            }
+            hatch.larva -= 1;
+            (UnitType::None, frame, base)
+        } else {
+            let Some(index) = self.units.iter().position(|u| {
+                u.unit_type == builder_type && u.complete <= frame && u.busy_until <= frame
+            }) else {
+                return;
+            };
+            let builder = self.units.remove(index);
+            let start = if builder_type.is_worker() {
+                frame
+                    + if base {
+                        EXPANSION_TRAVEL_FRAMES
+                    } else {
+                        BUILDER_TRAVEL_FRAMES
+                    }
+            } else {
+                frame
+            };
+            (
+                if builder_type.is_building() {
+                    builder_type
+                } else {
+                    UnitType::None
+                },
+                start,
+                base || builder.base,
+            )
+        };
+        self.minerals -= price.minerals as f64;
+        self.gas -= price.gas as f64;
+        let complete = start + unit_type.build_time();
+        for _ in 0..units_per_egg {
+            self.units.push(SimUnit {
+                unit_type,
+                morphed_from,
+                complete,
+                busy_until: complete,
+                base,
+            });
+        }
+        self.events.push(SimEvent {
+            item: SimItem::Unit(unit_type),
+            start,
+            complete,
+        });
+    }
+
+    // Reserves the builder (and money) if it is available
+    fn start_research(&mut self, item: SimItem, researcher: UnitType, price: Gms, time: i32) {
+        let frame = self.frame;
+        self.queued_gas += price.gas;
+        if !self.available.checked_sub(price) {
+            return;
+        }
+        let Some(researcher) = self.units.iter_mut().find(|u| {
+            u.unit_type.is_successor_of(researcher) && u.complete <= frame && u.busy_until <= frame
+        }) else {
+            return;
+        };
+        researcher.busy_until = frame + time;
+        self.minerals -= price.minerals as f64;
+        self.gas -= price.gas as f64;
+        self.events.push(SimEvent {
+            item,
+            start: frame,
+            complete: frame + time,
+        });
+    }
+
+    fn start_upgrade(&mut self, upgrade: UpgradeType, level: i32) {
+        let current = self
+            .upgrades
+            .iter()
+            .filter(|u| u.0 == upgrade)
+            .map(|u| u.1)
+            .max()
+            .unwrap_or(0);
+        if current >= level {
+            return;
+        }
+        let next = current + 1;
+        let required = upgrade.whats_required(next);
+        if required != UnitType::None && self.completed(required) == 0 {
+            return;
+        }
+        let events = self.events.len();
+        self.start_research(
+            SimItem::Upgrade(upgrade, next),
+            upgrade.what_upgrades(),
+            upgrade.price(next),
+            upgrade.upgrade_time(next),
+        );
+        if let Some(event) = self.events.get(events) {
+            self.upgrades.push((upgrade, next, event.complete));
+        }
+    }
+
+    fn start_tech(&mut self, tech: TechType) {
+        if self.techs.iter().any(|t| t.0 == tech) {
+            return;
+        }
+        let required = tech.required_unit();
+        if required != UnitType::None && self.completed(required) == 0 {
+            return;
+        }
+        let events = self.events.len();
+        self.start_research(
+            SimItem::Tech(tech),
+            tech.what_researches(),
+            tech.price(),
+            tech.research_time(),
+        );
+        if let Some(event) = self.events.get(events) {
+            self.techs.push((tech, event.complete));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(events: &[SimEvent], unit_type: UnitType) -> Vec<SimEvent> {
+        events
+            .iter()
+            .filter(|e| e.item == SimItem::Unit(unit_type))
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn should_be_supply_blocked_without_overlords() {
+        let mut sim = EconSim::default();
+
+        let events = sim.run(
+            &[BuildStep::Units {
+                unit_type: UnitType::Zerg_Drone,
+                count: 12,
+            }],
+            5000,
+        );
+
+        // 4 drones and an overlord on 9 supply
+        assert_eq!(started(events, UnitType::Zerg_Drone).len(), 5);
+    }
+
+    #[test]
+    fn should_build_pool_and_lings_in_order() {
+        let mut sim = EconSim::default();
+
+        let events = sim.run(
+            &[
+                BuildStep::Units {
+                    unit_type: UnitType::Zerg_Drone,
+                    count: 9,
+                },
+                BuildStep::Buildings {
+                    unit_type: UnitType::Zerg_Spawning_Pool,
+                    count: 1,
+                },
+                BuildStep::Units {
+                    unit_type: UnitType::Zerg_Drone,
+                    count: 8,
+                },
+                BuildStep::FreeSupply(2),
+                BuildStep::Units {
+                    unit_type: UnitType::Zerg_Zergling,
+                    count: 6,
+                },
+            ],
+            4000,
+        );
+
+        let pool = started(events, UnitType::Zerg_Spawning_Pool)[0];
+        let lings = started(events, UnitType::Zerg_Zergling);
+        assert_eq!(
+            pool.complete - pool.start,
+            UnitType::Zerg_Spawning_Pool.build_time()
+        );
+        assert_eq!(lings.len(), 3);
+        assert!(lings.iter().all(|l| l.start >= pool.complete));
+    }
+
+    #[test]
+    fn pool_first_should_get_lings_out_earlier_than_hatch_first() {
+        let lings_at = |steps: &[BuildStep]| {
+            let mut sim = EconSim::default();
+            started(sim.run(steps, 6000), UnitType::Zerg_Zergling)[0].complete
+        };
+        let lings = BuildStep::Units {
+            unit_type: UnitType::Zerg_Zergling,
+            count: 2,
+        };
+        let pool = BuildStep::Buildings {
+            unit_type: UnitType::Zerg_Spawning_Pool,
+            count: 1,
+        };
+        let supply = BuildStep::FreeSupply(2);
+
+        let nine_pool = lings_at(&[
+            BuildStep::Units {
+                unit_type: UnitType::Zerg_Drone,
+                count: 9,
+            },
+            pool.clone(),
+            supply.clone(),
+            lings.clone(),
+        ]);
+        let twelve_hatch = lings_at(&[
+            BuildStep::Units {
+                unit_type: UnitType::Zerg_Drone,
+                count: 9,
+            },
+            supply.clone(),
+            BuildStep::Units {
+                unit_type: UnitType::Zerg_Drone,
+                count: 12,
+            },
+            BuildStep::Bases(2),
+            pool,
+            supply,
+            lings,
+        ]);
+
+        assert!(nine_pool < twelve_hatch);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::sbase::SBase;
+use crate::*;
+
+// Resources this close to a base location belong to it
+const BASE_RESOURCE_RADIUS: i32 = 10 * 32;
+// Chokes this close to a base location are entrances to it
+const ENTRANCE_RADIUS: f64 = 18.0 * 32.0;
+// Enemy army units seen near a base within this many frames make it unsafe
+const THREAT_FRAMES: i32 = 24 * 60;
+const THREAT_RADIUS: i32 = 12 * 32;
+// Chokes narrower than this (in tiles) are easily held
+const NARROW_CHOKE_WIDTH: i32 = 6;
+const MAX_CHOKE_SCAN: i32 = 16;
+
+// Not worth taking anymore
+const MIN_MINERALS: i32 = 1500;
+
+const MINERAL_WEIGHT: f64 = 1.0 / 1000.0;
+const GAS_WEIGHT: f64 = 1.5 / 1000.0;
+// Per tile of ground distance
+const HOME_DISTANCE_WEIGHT: f64 = 0.15;
+const ENEMY_DISTANCE_WEIGHT: f64 = 0.05;
+const MAX_ENEMY_DISTANCE: f64 = 100.0;
+const ENTRANCE_WEIGHT: f64 = 2.0;
+const CHOKE_WIDTH_WEIGHT: f64 = 0.2;
+const THREAT_WEIGHT: f64 = 3.0;
+
+#[derive(Clone, Debug)]
+pub struct ExpansionSite {
+    pub position: TilePosition,
+    pub minerals: i32,
+    pub gas: i32,
+    // Ground distances in tiles
+    pub home_distance: f64,
+    pub enemy_distance: Option<f64>,
+    // Width in tiles of the widest entrance
+    pub choke_width: i32,
+    pub entrances: usize,
+    pub island: bool,
+    // Enemy army units seen near the base recently
+    pub threats: usize,
+}
+
+impl ExpansionSite {
+    // Higher is better, `None` if the site should not be taken at all
+    pub fn score(&self) -> Option<f64> {
+        if self.island || self.minerals < MIN_MINERALS {
+            return None;
+        }
+        let enemy_distance = self
+            .enemy_distance
+            .unwrap_or(MAX_ENEMY_DISTANCE)
+            .min(MAX_ENEMY_DISTANCE);
+        Some(
+            self.minerals as f64 * MINERAL_WEIGHT + self.gas as f64 * GAS_WEIGHT
+                - self.home_distance * HOME_DISTANCE_WEIGHT
+                + enemy_distance * ENEMY_DISTANCE_WEIGHT
+                - self.entrances.saturating_sub(1) as f64 * ENTRANCE_WEIGHT
+                - (self.choke_width - NARROW_CHOKE_WIDTH).max(0) as f64 * CHOKE_WIDTH_WEIGHT
+                - self.threats as f64 * THREAT_WEIGHT,
+        )
+    }
+}
+
+// Width in tiles of the walkable cross-section through `center`, the narrower of the horizontal
+// and vertical scan.
+pub fn choke_width(center: TilePosition, walkable: impl Fn(TilePosition) -> bool) -> i32 {
+    let scan = |dx: i32, dy: i32| {
+        (1..=MAX_CHOKE_SCAN)
+            .take_while(|&i| walkable(TilePosition::new(center.x + dx * i, center.y + dy * i)))
+            .count() as i32
+    };
+    1 + (scan(1, 0) + scan(-1, 0)).min(scan(0, 1) + scan(0, -1))
+}
+
+impl MyModule {
+    fn expansion_site(
+        &self,
+        base: &SBase,
+        home: Position,
+        enemy: Option<Position>,
+    ) -> ExpansionSite {
+        let position = base.position;
+        let center = position.center();
+        // Until we had a look, assume the base is untouched
+        let resources = |check: fn(UnitType) -> bool| {
+            if base.last_explored < 0 {
+                self.game
+                    .get_static_minerals()
+                    .iter()
+                    .chain(self.game.get_static_geysers().iter())
+                    .filter(|u| {
+                        check(u.get_initial_type())
+                            && u.get_initial_position().distance(center)
+                                < BASE_RESOURCE_RADIUS as f64
+                    })
+                    .map(|u| u.get_initial_resources())
+                    .sum::<i32>()
+            } else {
+                self.units
+                    .all_in_radius(center, BASE_RESOURCE_RADIUS)
+                    .filter(|u| check(u.get_type()))
+                    .map(|u| u.resources())
+                    .sum::<i32>()
+            }
+        };
+        let island = !self.game.has_path(home, center);
+        let home_distance = self.map.get_path(home, center).1 as f64 / 32.0;
+        let enemy_distance = enemy.map(|e| self.map.get_path(center, e).1 as f64 / 32.0);
+        let entrances: Vec<_> = self
+            .map
+            .choke_points
+            .iter()
+            .map(|cp| cp.top.center())
+            .filter(|c| c.distance(center) < ENTRANCE_RADIUS)
+            .collect();
+        let choke_width = entrances
+            .iter()
+            .map(|c| {
+                choke_width(c.to_tile_position(), |t| {
+                    t.is_valid(&&self.game) && self.game.is_walkable(t.center().to_walk_position())
+                })
+            })
+            .max()
+            .unwrap_or(0);
+        let frame = self.game.get_frame_count();
+        let threats = self
+            .units
+            .enemy
+            .iter()
+            .filter(|e| {
+                frame - e.last_seen() < THREAT_FRAMES
+                    && !e.get_type().is_worker()
+                    && e.get_type().can_attack()
+                    && e.position().distance(center) < THREAT_RADIUS as f64
+            })
+            .count();
+        ExpansionSite {
+            position,
+            minerals: resources(|ut| ut.is_mineral_field()),
+            gas: resources(|ut| ut == UnitType::Resource_Vespene_Geyser || ut.is_refinery()),
+            home_distance,
+            enemy_distance,
+            choke_width,
+            entrances: entrances.len(),
+            island,
+            threats,
+        }
+    }
+
+    // Best free base location to expand to, if any is left
+    pub fn best_expansion(&self) -> Option<ExpansionSite> {
+        let home = self.main_base()?.position();
+        let enemy = self
+            .bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false))
+            .map(|b| b.position.center())
+            .min_by_key(|p| self.map.get_path(home, *p).1);
+        let mut sites: Vec<_> = self
+            .bases
+            .all()
+            .filter(|candidate| candidate.resource_depot.is_none())
+            .map(|candidate| self.expansion_site(candidate, home, enemy))
+            .filter_map(|site| site.score().map(|score| (site, score)))
+            .collect();
+        sites.sort_by_key(|(_, score)| std::cmp::Reverse(OrderedFloat(*score)));
+        for (site, score) in sites.iter() {
+            cvis().draw_text(
+                site.position.center().x,
+                site.position.center().y,
+                format!("exp: {:.1}", score),
+            );
+        }
+        sites.into_iter().next().map(|(site, _)| site)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> ExpansionSite {
+        ExpansionSite {
+            position: TilePosition::new(0, 0),
+            minerals: 8 * 1500,
+            gas: 5000,
+            home_distance: 40.0,
+            enemy_distance: Some(100.0),
+            choke_width: 4,
+            entrances: 1,
+            island: false,
+            threats: 0,
+        }
+    }
+
+    #[test]
+    fn should_prefer_close_and_safe_sites() {
+        let close = site();
+        let far = ExpansionSite {
+            home_distance: 80.0,
+            ..site()
+        };
+        let threatened = ExpansionSite {
+            threats: 4,
+            ..site()
+        };
+        let open = ExpansionSite {
+            entrances: 3,
+            choke_width: 14,
+            ..site()
+        };
+
+        let best = close.score().unwrap();
+        assert!(best > far.score().unwrap());
+        assert!(best > threatened.score().unwrap());
+        assert!(best > open.score().unwrap());
+    }
+
+    #[test]
+    fn should_skip_islands_and_mined_out_sites() {
+        let island = ExpansionSite {
+            island: true,
+            ..site()
+        };
+        let mined_out = ExpansionSite {
+            minerals: 300,
+            ..site()
+        };
+
+        assert_eq!(island.score(), None);
+        assert_eq!(mined_out.score(), None);
+    }
+
+    #[test]
+    fn should_measure_choke_width() {
+        // Corridor open for y in 10..15
+        let walkable = |t: TilePosition| t.y >= 10 && t.y < 15;
+
+        assert_eq!(choke_width(TilePosition::new(20, 12), walkable), 5);
+    }
+}
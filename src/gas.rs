@@ -0,0 +1,191 @@
+use crate::*;
+use ahash::AHashMap;
+
+// Gas we need is gathered within this many frames
+pub const GAS_HORIZON_FRAMES: i32 = 24 * 60;
+pub const WORKERS_PER_REFINERY: usize = 3;
+// Only take drones off gas if we have at least this many too much
+const GAS_HYSTERESIS: usize = 2;
+// Minimum frames between changes of the number of drones on gas
+pub const MIN_SWITCH_FRAMES: i32 = 24 * 4;
+
+// Drones on gas, kept across frames
+#[derive(Default)]
+pub struct GasController {
+    // Drone -> refinery
+    pub assignments: AHashMap<SUnit, SUnit>,
+    params: GatherParams,
+    last_change: i32,
+    kept_frame: i32,
+}
+
+// Drones required to gather `shortfall` gas, if each gathers `per_worker` gas
+pub fn gas_workers_needed(shortfall: i32, per_worker: i32) -> usize {
+    if shortfall <= 0 {
+        0
+    } else {
+        ((shortfall + per_worker.max(1) - 1) / per_worker.max(1)) as usize
+    }
+}
+
+// Additional drones are added right away, but only removed if there are clearly too many
+pub fn gas_workers_wanted(current: usize, needed: usize, max: usize) -> usize {
+    let needed = needed.min(max);
+    if needed > current || current > max || current >= needed + GAS_HYSTERESIS {
+        needed
+    } else {
+        current
+    }
+}
+
+impl MyModule {
+    // Strategies can limit the drones on gas and ask for gas beyond what is queued. Only holds
+    // for the current frame.
+    pub fn ensure_gathering_gas(&mut self, gather_params: GatherParams) {
+        self.gas.params = gather_params;
+        self.keep_gas_workers();
+    }
+
+    // Drones already on gas keep gathering, unless someone else needed them
+    fn keep_gas_workers(&mut self) {
+        let frame = self.game.get_frame_count();
+        if self.gas.kept_frame == frame {
+            return;
+        }
+        self.gas.kept_frame = frame;
+        let assignments = std::mem::take(&mut self.gas.assignments);
+        for (w, refinery) in assignments {
+            if !w.exists() || !refinery.exists() || !refinery.player().is_me() {
+                continue;
+            }
+            if self.tracker.try_reserve_unit(&w).is_none() {
+                continue;
+            }
+            if !w.carrying() {
+                w.gather(&refinery).ok();
+            }
+            self.gas.assignments.insert(w, refinery);
+        }
+    }
+
+    // Derives the drones needed on gas from what is queued for production
+    pub fn perform_gas_gathering(&mut self) {
+        self.keep_gas_workers();
+        let refineries: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|m| m.remaining_build_time() < 24 && m.get_type().is_refinery())
+            .cloned()
+            .collect();
+        self.gas
+            .assignments
+            .retain(|_, refinery| refineries.contains(refinery));
+        let depots: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| u.get_type().is_resource_depot())
+            .collect();
+        let per_worker = refineries
+            .iter()
+            .filter_map(|r| {
+                let distance = depots.iter().map(|d| d.distance_to(r)).min()? as f64;
+                Some(
+                    ResourceIncome {
+                        kind: ResourceKind::Gas,
+                        distance,
+                        workers: 1,
+                        remaining: r.resources(),
+                    }
+                    .income(GAS_HORIZON_FRAMES),
+                )
+            })
+            .max()
+            .unwrap_or(0);
+        let GatherParams {
+            required_resources,
+            max_workers,
+        } = std::mem::take(&mut self.gas.params);
+        let gas = self.game.self_().unwrap().gas();
+        let shortfall = self.queued_gas().saturating_add(required_resources.max(0)) - gas;
+        let max = (refineries.len() * WORKERS_PER_REFINERY).min(max_workers.max(0) as usize);
+        let current = self.gas.assignments.len();
+        let wanted = gas_workers_wanted(current, gas_workers_needed(shortfall, per_worker), max);
+        let frame = self.game.get_frame_count();
+        if wanted == current || current <= max && frame - self.gas.last_change < MIN_SWITCH_FRAMES {
+            return;
+        }
+        self.gas.last_change = frame;
+        cvis().log(|| {
+            format!(
+                "Drones on gas: {} -> {}, short {} gas",
+                current, wanted, shortfall
+            )
+        });
+
+        if wanted < current {
+            let mut drones: Vec<_> = self.gas.assignments.keys().cloned().collect();
+            // Let drones carrying gas bring it home
+            drones.sort_by_key(|w| w.carrying_gas());
+            for w in drones.into_iter().take(current - wanted) {
+                self.gas.assignments.remove(&w);
+                self.tracker.available_units.push(w);
+            }
+            return;
+        }
+        for _ in current..wanted {
+            let Some(refinery) = refineries
+                .iter()
+                .map(|r| {
+                    let workers = self.gas.assignments.values().filter(|it| *it == r).count();
+                    (r, workers)
+                })
+                .filter(|(_, workers)| *workers < WORKERS_PER_REFINERY)
+                .min_by_key(|(_, workers)| *workers)
+                .map(|(r, _)| r)
+            else {
+                break;
+            };
+            let Some(w) = self
+                .tracker
+                .available_units
+                .iter()
+                .filter(|u| u.get_type().is_worker())
+                .min_by_key(|w| w.distance_to(refinery) + (w.carrying_minerals() as i32) * 300)
+                .cloned()
+            else {
+                break;
+            };
+            w.gather(refinery).ok();
+            self.tracker.reserve_unit(&w);
+            self.gas.assignments.insert(w, refinery.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_add_gas_workers_right_away() {
+        assert_eq!(gas_workers_wanted(0, 3, 6), 3);
+        assert_eq!(gas_workers_wanted(3, 4, 6), 4);
+        assert_eq!(gas_workers_wanted(3, 8, 6), 6);
+    }
+
+    #[test]
+    fn should_not_oscillate() {
+        assert_eq!(gas_workers_wanted(3, 2, 6), 3);
+        assert_eq!(gas_workers_wanted(3, 1, 6), 1);
+        assert_eq!(gas_workers_wanted(6, 6, 3), 3);
+    }
+
+    #[test]
+    fn should_compute_workers_for_shortfall() {
+        assert_eq!(gas_workers_needed(-50, 100), 0);
+        assert_eq!(gas_workers_needed(150, 100), 2);
+        assert_eq!(gas_workers_needed(99999, 100), 1000);
+    }
+}
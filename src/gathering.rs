@@ -1,7 +1,18 @@
 use crate::*;
+use ahash::{AHashMap, AHashSet};
+
+// Minerals further away (in tiles) from a completed depot are not mined
+const MINERAL_DEPOT_RADIUS: i32 = 9;
+// Patches are shared by at most this many drones - unless there are too many drones
+const MAX_WORKERS_PER_PATCH: usize = 2;
+// Sharing a patch is a bit worse, even if the other drone is away returning cargo
+const SHARED_PATCH_PENALTY: i32 = 12;
+const EFFICIENCY_REPORT_FRAMES: i32 = 24 * 60;
+const MINERALS_PER_TRIP: i32 = 8;
 
 #[derive(Copy, Clone)]
 pub struct GatherParams {
+    // Gas to gather in addition to what queued production needs
     pub required_resources: i32,
     pub max_workers: i32,
 }
@@ -9,116 +20,63 @@ pub struct GatherParams {
 impl Default for GatherParams {
     fn default() -> Self {
         Self {
-            required_resources: 99999,
+            required_resources: 0,
             max_workers: 99999,
         }
     }
 }
 
-impl MyModule {
-    pub fn estimate_gas(&self, frames: i32, sub_workers: i32) -> i32 {
-        (self
-            .units
-            .my_completed
-            .iter()
-            .filter(|it| it.gathering_gas())
-            .count() as i32)
-            .saturating_sub(sub_workers)
-            * 69
-            * frames
-            / 1000
-    }
+// Which drone mines which patch, kept across frames
+#[derive(Default)]
+pub struct Mining {
+    pub assignments: AHashMap<SUnit, SUnit>,
+    // Drones carrying minerals last frame
+    carrying: AHashSet<SUnit>,
+    // Trips completed per depot since the last report
+    trips: AHashMap<SUnit, i32>,
+    report_frame: i32,
+}
 
-    pub fn estimate_minerals(&self, frames: i32, sub_workers: i32) -> i32 {
-        (self
-            .units
-            .my_completed
-            .iter()
-            .filter(|it| it.gathering_minerals())
-            .count() as i32)
-            .saturating_sub(sub_workers)
-            * 47
-            * frames
-            / 1000
-    }
+// Frames until a drone arriving in `travel` frames can start mining. `harvesting` are the remaining
+// mining frames of the drone currently at the patch.
+pub fn patch_cost(travel: i32, harvesting: Option<i32>, sharing: usize) -> i32 {
+    travel.max(harvesting.unwrap_or(0)) + sharing as i32 * SHARED_PATCH_PENALTY
+}
 
-    pub fn estimate_gms(&self, frames: i32, sub_workers: i32) -> Gms {
-        Gms {
-            minerals: self.estimate_minerals(frames, sub_workers),
-            // TODO: Only subtract workers from miners?
-            gas: self.estimate_gas(frames, 0),
-            supply: 0,
-        }
-    }
-
-    pub fn ensure_gathering_gas(&mut self, gather_params: GatherParams) {
-        let GatherParams {
-            required_resources: required,
-            max_workers,
-        } = gather_params;
-        let game = &self.game;
-        let units = self.units.all();
-        let mut remaining_required = ((0.max(required) + 7) / 8) as usize;
-        let mut remaining_workers = max_workers as usize;
-        for refinery in self
-            .units
-            .my_completed
-            .iter()
-            .filter(|m| m.remaining_build_time() < 24 && m.get_type().is_refinery())
-        {
-            let mut gas_workers: Vec<_> = self
-                .tracker
-                .available_units
-                .iter()
-                .cloned()
-                .filter(|u| u.get_type().is_worker())
-                .collect();
-            gas_workers.sort_by_key(|w| {
-                (!w.gathering_gas() as i32) * 1000
-                    + w.distance_to(refinery)
-                    + (w.carrying_minerals() as i32) * 300
-            });
-            let gas_workers: Vec<_> = gas_workers
-                .iter()
-                // TODO decide 3 or 4 workers based on base <-> refinery distance
-                .take(remaining_required.clamp(0, 3.min(remaining_workers)))
-                .cloned()
-                .collect();
-            // TODO: subtract only 2 for depleted
-            remaining_required = remaining_required.saturating_sub(gas_workers.len() * 8);
-            remaining_workers = remaining_workers.saturating_sub(gas_workers.len());
-            for w in gas_workers {
-                if (!w.gathering() || !w.carrying()) && w.target().as_ref() != Some(refinery)
-                    || !w.gathering_gas()
-                {
-                    w.gather(refinery).ok();
-                }
-                self.tracker.reserve_unit(&w);
-            }
-        }
+// Gathered minerals relative to what the assigned drones should have gathered
+pub fn mining_efficiency(gathered: i32, expected: f64) -> f64 {
+    if expected <= 0.0 {
+        1.0
+    } else {
+        gathered as f64 / expected
     }
+}
 
+impl MyModule {
+    // Drones are locked onto their patch until they are needed elsewhere or the patch is gone.
+    // New drones pick the patch where they can start mining the earliest.
     pub fn ensure_gathering_minerals(&mut self) {
-        let game = &self.game;
-        let units = self.units.all();
-
-        let bases: Vec<_> = self
+        let depots: Vec<_> = self
             .units
             .my_completed
             .iter()
             .filter(|it| it.get_type().is_resource_depot() && it.completed())
+            .cloned()
             .collect();
-
-        let mut minerals: Vec<_> = units
+        let minerals: Vec<_> = self
+            .units
+            .all()
             .filter(|m| {
                 m.get_type().is_mineral_field()
                     && m.visible()
-                    && bases
-                        .iter()
-                        .any(|b| b.tile_position().distance(m.tile_position()) < 9.0 * 9.0)
+                    && depots.iter().any(|b| {
+                        b.tile_position().distance_squared(m.tile_position())
+                            < MINERAL_DEPOT_RADIUS * MINERAL_DEPOT_RADIUS
+                    })
             })
+            .cloned()
             .collect();
-        let mut miners: Vec<_> = self
+        let miners: Vec<_> = self
             .tracker
             .available_units
             .iter()
@@ -128,87 +86,165 @@ impl MyModule {
         for w in miners.iter() {
             self.tracker.reserve_unit(w);
         }
-        miners.retain(|w| {
-            if matches!(w.get_order(), Order::MiningMinerals) {
-                // let Some(mineral_index) = minerals
-                //         .iter()
-                //         .position(|m| Some(*m) == w.get_order_target().as_ref())
-                //     else {
-                //         cvis().log(|| "Worker target mineral not found");
-                //         return true;
-                //     };
-                // let mineral = minerals.swap_remove(mineral_index);
-                // if DRAW_GATHERING_TARGET {
-                //     cvis().draw_line(
-                //         w.position().x,
-                //         w.position().y,
-                //         mineral.position().x,
-                //         mineral.position().y,
-                //         Color::Green,
-                //     );
-                //     // cvis().draw_text(
-                //     //     w.position().x,
-                //     //     w.position().y,
-                //     //     format!("O:{:?}/{:?}", w.get_order(), w.get_secondary_order()),
-                //     // );
-                // }
-                return false;
-            } else if w.get_order() == Order::ReturnMinerals {
-                return false;
+
+        // Forget drones used for something else and patches which are gone
+        self.mining
+            .assignments
+            .retain(|w, m| w.exists() && miners.contains(w) && minerals.contains(m));
+        self.mining.carrying.retain(|w| miners.contains(w));
+        for w in miners.iter() {
+            if !w.carrying_minerals() {
+                self.mining.carrying.remove(w);
+            } else if self.mining.carrying.insert(w.clone()) {
+                // Just finished mining
+                if let Some(depot) = self
+                    .mining
+                    .assignments
+                    .get(w)
+                    .and_then(|m| depots.iter().min_by_key(|d| d.distance_to(m)))
+                {
+                    *self.mining.trips.entry(depot.clone()).or_default() += 1;
+                }
+            }
+        }
+
+        // Keep drones on their patch, otherwise they'll bounce to the next one if it's in use
+        for (w, m) in self.mining.assignments.iter() {
+            // Drones already mining their patch are left alone, reissuing would restart mining
+            if w.idle() || !w.carrying() && w.get_order_target().as_ref() != Some(m) {
+                w.gather(m).ok();
             }
-            return true;
-        });
+        }
 
-        while !miners.is_empty() {
-            let miner_mineral = miners
+        let mut unassigned: Vec<_> = miners
+            .iter()
+            .filter(|w| !self.mining.assignments.contains_key(*w))
+            .cloned()
+            .collect();
+        let max_per_patch = if minerals.is_empty() {
+            MAX_WORKERS_PER_PATCH
+        } else {
+            MAX_WORKERS_PER_PATCH.max((miners.len() + minerals.len() - 1) / minerals.len())
+        };
+        while !unassigned.is_empty() {
+            let sharing = |m: &SUnit| {
+                self.mining
+                    .assignments
+                    .values()
+                    .filter(|it| *it == m)
+                    .count()
+            };
+            let best = unassigned
                 .iter()
                 .enumerate()
-                .filter_map(|(i, u)| {
-                    minerals
-                        .iter()
-                        .enumerate()
-                        .map(|(j, m)| {
-                            (
-                                j,
-                                m,
-                                self.estimate_frames_to(u, m.position())
-                                    + m.remaining_mining_frames()
-                                    + if u.get_order_target().as_ref() == Some(m) {
-                                        0
-                                    } else {
-                                        12
-                                    },
-                            )
-                        })
-                        .min_by_key(|(_, m, d)| *d)
-                        .map(|(j, m, d)| (i, u, j, m, d))
-                })
-                .min_by_key(|(.., d)| *d);
-            if let Some((i, w, j, m, _)) = miner_mineral {
-                if DRAW_GATHERING_TARGET {
-                    cvis().draw_line(
-                        w.position().x,
-                        w.position().y,
-                        m.position().x,
-                        m.position().y,
-                        Color::Green,
+                .flat_map(|(i, w)| minerals.iter().map(move |m| (i, w, m)))
+                .filter(|(_, _, m)| sharing(m) < max_per_patch)
+                .map(|(i, w, m)| {
+                    let harvesting = if m.being_gathered() {
+                        Some(m.remaining_mining_frames())
+                    } else {
+                        None
+                    };
+                    let cost = patch_cost(
+                        self.estimate_frames_to(w, m.position()),
+                        harvesting,
+                        sharing(m),
                     );
-                    // cvis().draw_text(
-                    //     w.position().x,
-                    //     w.position().y,
-                    //     format!("O:{:?}/{:?}", w.get_order(), w.get_secondary_order()),
-                    // );
-                }
-                w.gather(m).ok();
-                miners.swap_remove(i);
-                // We might want multiple workers on one mineral
-                if miners.len() <= minerals.len() {
-                    minerals.swap_remove(j);
-                }
-            } else {
+                    (i, m, cost)
+                })
+                .min_by_key(|(.., cost)| *cost);
+            let Some((i, m, _)) = best else {
                 // TODO No minerals? Make workers attack as well I guess?
                 break;
+            };
+            let w = unassigned.swap_remove(i);
+            w.gather(m).ok();
+            self.mining.assignments.insert(w, m.clone());
+        }
+
+        if DRAW_GATHERING_TARGET {
+            for (w, m) in self.mining.assignments.iter() {
+                cvis().draw_line(
+                    w.position().x,
+                    w.position().y,
+                    m.position().x,
+                    m.position().y,
+                    Color::Green,
+                );
             }
         }
+        self.report_mining_efficiency(&depots);
+    }
+
+    fn report_mining_efficiency(&mut self, depots: &[SUnit]) {
+        let frame = self.game.get_frame_count();
+        let elapsed = frame - self.mining.report_frame;
+        if elapsed < EFFICIENCY_REPORT_FRAMES {
+            return;
+        }
+        for depot in depots {
+            let mut patches: AHashMap<&SUnit, i32> = AHashMap::new();
+            for (_, m) in self.mining.assignments.iter() {
+                if depots.iter().min_by_key(|d| d.distance_to(m)) == Some(depot) {
+                    *patches.entry(m).or_default() += 1;
+                }
+            }
+            let expected: f64 = patches
+                .iter()
+                .map(|(m, &workers)| {
+                    ResourceIncome {
+                        kind: ResourceKind::Minerals,
+                        distance: m.position().distance(depot.position()),
+                        workers,
+                        remaining: m.resources(),
+                    }
+                    .rate()
+                        * elapsed as f64
+                })
+                .sum();
+            let gathered = self.mining.trips.get(depot).copied().unwrap_or(0) * MINERALS_PER_TRIP;
+            cvis().log(|| {
+                format!(
+                    "Base {}: {} drones, gathered {} minerals, efficiency {:.0}%",
+                    depot.tile_position(),
+                    patches.values().sum::<i32>(),
+                    gathered,
+                    mining_efficiency(gathered, expected) * 100.0
+                )
+            });
+        }
+        self.mining.trips.clear();
+        self.mining.report_frame = frame;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_prefer_free_patch() {
+        let free = patch_cost(40, None, 0);
+        let shared = patch_cost(40, None, 1);
+
+        assert!(free < shared);
+    }
+
+    #[test]
+    fn should_wait_for_close_patch_being_mined() {
+        // The other drone is almost done, better than walking to the far patch
+        let close = patch_cost(20, Some(30), 1);
+        let far = patch_cost(60, None, 0);
+        // ... but not if it just started mining
+        let busy = patch_cost(20, Some(80), 1);
+
+        assert!(close < far);
+        assert!(busy > far);
+    }
+
+    #[test]
+    fn should_rate_efficiency() {
+        assert_eq!(mining_efficiency(400, 800.0), 0.5);
+        assert_eq!(mining_efficiency(0, 0.0), 1.0);
     }
 }
@@ -1,5 +1,5 @@
 use derive_more::{Add, AddAssign, Display, Div, DivAssign, Sub, SubAssign, Sum};
-use rsbwapi::{Unit, UnitType, UpgradeType};
+use rsbwapi::{TechType, Unit, UnitType, UpgradeType};
 use std::cmp::Ordering;
 use std::ops::*;
 
@@ -132,6 +132,16 @@ impl Price for UnitType {
     }
 }
 
+impl Price for TechType {
+    fn price(&self) -> Gms {
+        Gms {
+            minerals: self.mineral_price(),
+            gas: self.gas_price(),
+            supply: 0,
+        }
+    }
+}
+
 pub trait LeveledPrice {
     fn price(&self, level: i32) -> Gms;
 }
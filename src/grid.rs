@@ -1,4 +1,6 @@
-use crate::Units;
+use crate::cherry_vis::*;
+use crate::config::*;
+use crate::{SUnit, Units};
 use rsbwapi::*;
 
 #[derive(Copy, Clone)]
@@ -73,7 +75,6 @@ impl<T: Copy, const N: usize> Grid<T, N> {
                 let dx_sq = dx.wrapping_mul(dx);
                 if dx_sq + dy_sq <= range_sq {
                     let tile = &mut self.tiles[j][i];
-                    tile.version = self.version;
                     tile.item = modifier(
                         if tile.version == self.version {
                             Some(tile.item)
@@ -83,6 +84,7 @@ impl<T: Copy, const N: usize> Grid<T, N> {
                         i,
                         j,
                     );
+                    tile.version = self.version;
                 }
             }
         }
@@ -90,16 +92,73 @@ impl<T: Copy, const N: usize> Grid<T, N> {
 }
 
 pub struct Grids {
-    // pub ground_threat: Grid<u16, 256>,
-    // pub air_threat: Grid<u16, 256>,
+    // Damage per frame enemies can deal to ground/air units on a tile
+    pub ground_threat: Grid<f32, 256>,
+    pub air_threat: Grid<f32, 256>,
+    // Tiles our units can see/detect
+    pub vision: Grid<bool, 256>,
+    pub detection: Grid<bool, 256>,
+    // Our damage per frame minus the enemy's, positive values are "ours"
+    pub control: Grid<f32, 256>,
     pub unit_walkability: Grid<UnitId, { 256 * 8 }>,
 }
 
+// Enemy units not seen for this many frames are no longer considered a threat, their influence
+// fades linearly until then
+const ENEMY_MEMORY_FRAMES: i32 = 24 * 20;
+
+// Range (in pixels) and damage per frame a unit can deal to ground or air targets, bunkers are
+// assumed to be loaded
+fn threat(unit: &SUnit, air: bool) -> Option<(i32, f32)> {
+    match unit.get_type() {
+        UnitType::Terran_Bunker => {
+            let weapon = WeaponType::Gauss_Rifle;
+            return Some((
+                UnitType::Terran_Marine.ground_weapon().max_range() + 64,
+                4.0 * weapon.damage_amount() as f32 / weapon.damage_cooldown() as f32,
+            ));
+        }
+        UnitType::Protoss_Reaver if !air => {
+            return Some((8 * 32, WeaponType::Scarab.damage_amount() as f32 / 60.0));
+        }
+        _ => (),
+    }
+    let weapon = if air {
+        unit.get_air_weapon()
+    } else {
+        unit.get_ground_weapon()
+    };
+    if weapon.weapon_type == WeaponType::None {
+        None
+    } else {
+        Some((
+            weapon.max_range,
+            weapon.damage as f32 / weapon.weapon_type.damage_cooldown().max(1) as f32,
+        ))
+    }
+}
+
+fn tile_of<const M: i32>(pos: ScaledPosition<M>) -> Option<(usize, usize)> {
+    if pos.x < 0 || pos.y < 0 {
+        None
+    } else {
+        Some(((pos.x * M / 32) as usize, (pos.y * M / 32) as usize))
+    }
+}
+
+// Radius in tiles for a range in pixels, with some buffer for the unit's size
+fn tile_radius(range: i32) -> usize {
+    (range + 32 + 31) as usize / 32
+}
+
 impl Grids {
     pub fn new() -> Self {
         Self {
-            // ground_threat: Grid::new(0),
-            // air_threat: Grid::new(0),
+            ground_threat: Grid::new(0.0),
+            air_threat: Grid::new(0.0),
+            vision: Grid::new(false),
+            detection: Grid::new(false),
+            control: Grid::new(0.0),
             unit_walkability: Grid::new(UnitId::MAX),
         }
     }
@@ -108,9 +167,50 @@ impl Grids {
         self.unit_walkability.get_pos(pos).copied()
     }
 
-    pub fn update(&mut self, units: &Units) {
-        // self.ground_threat.reset();
-        // self.air_threat.reset();
+    fn value_at<T: Copy, const M: i32>(
+        grid: &Grid<T, 256>,
+        pos: ScaledPosition<M>,
+        default: T,
+    ) -> T {
+        tile_of(pos)
+            .and_then(|(x, y)| grid.get(x, y).copied())
+            .unwrap_or(default)
+    }
+
+    pub fn ground_threat_at<const M: i32>(&self, pos: ScaledPosition<M>) -> f32 {
+        Self::value_at(&self.ground_threat, pos, 0.0)
+    }
+
+    pub fn air_threat_at<const M: i32>(&self, pos: ScaledPosition<M>) -> f32 {
+        Self::value_at(&self.air_threat, pos, 0.0)
+    }
+
+    pub fn threat_at<const M: i32>(&self, pos: ScaledPosition<M>, flying: bool) -> f32 {
+        if flying {
+            self.air_threat_at(pos)
+        } else {
+            self.ground_threat_at(pos)
+        }
+    }
+
+    pub fn is_visible_at<const M: i32>(&self, pos: ScaledPosition<M>) -> bool {
+        Self::value_at(&self.vision, pos, false)
+    }
+
+    pub fn is_detected_at<const M: i32>(&self, pos: ScaledPosition<M>) -> bool {
+        Self::value_at(&self.detection, pos, false)
+    }
+
+    pub fn control_at<const M: i32>(&self, pos: ScaledPosition<M>) -> f32 {
+        Self::value_at(&self.control, pos, 0.0)
+    }
+
+    pub fn update(&mut self, frame: i32, units: &Units) {
+        self.ground_threat.reset();
+        self.air_threat.reset();
+        self.vision.reset();
+        self.detection.reset();
+        self.control.reset();
         self.unit_walkability.reset();
 
         for u in units.all().filter(|u| !u.flying()) {
@@ -122,27 +222,90 @@ impl Grids {
                 self.unit_walkability.set_pos(wp, u.id());
             }
         }
-        // threat maps
-        // for e in &units.enemy {
-        //     let (x, y) = e.tile_position().into();
-        //     let range = (e.get_ground_weapon().max_range + 31) / 32;
-        //     if e.get_type().ground_weapon() != WeaponType::None {
-        //         self.ground_threat.modify_in_range(
-        //             x as usize,
-        //             y as usize,
-        //             range as usize,
-        //             |i, _, _| i.unwrap_or(0) + 1,
-        //         );
-        //     }
-        //     if e.get_type().air_weapon() != WeaponType::None {
-        //         self.air_threat.modify_in_range(
-        //             x as usize,
-        //             y as usize,
-        //             range as usize,
-        //             |i, _, _| i.unwrap_or(0) + 1,
-        //         );
-        //     }
-        // }
+
+        // Parasited enemies are our eyes as well
+        for u in units.parasited.iter() {
+            let Some((x, y)) = tile_of(u.tile_position()) else {
+                continue;
+            };
+            let sight = (u.get_type().sight_range() / 32) as usize;
+            self.vision.modify_in_range(x, y, sight, |_, _, _| true);
+        }
+        for u in units.mine_all.iter().filter(|u| u.completed()) {
+            let Some((x, y)) = tile_of(u.tile_position()) else {
+                continue;
+            };
+            let sight = (u.get_type().sight_range() / 32) as usize;
+            self.vision.modify_in_range(x, y, sight, |_, _, _| true);
+            if u.get_type().is_detector() {
+                self.detection.modify_in_range(x, y, sight, |_, _, _| true);
+            }
+            let dpf = [false, true]
+                .into_iter()
+                .filter_map(|air| threat(u, air))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((range, dpf)) = dpf {
+                self.control
+                    .modify_in_range(x, y, tile_radius(range), |i, _, _| i.unwrap_or(0.0) + dpf);
+            }
+        }
+
+        // threat maps - including enemies we don't see right now
+        for e in units
+            .enemy
+            .iter()
+            .filter(|e| e.completed() && !e.get_type().is_worker())
+        {
+            let Some((x, y)) = tile_of(e.tile_position()) else {
+                continue;
+            };
+            // Static defense will still be there when we arrive, mobile units will have moved
+            let weight = if !e.get_type().can_move() {
+                1.0
+            } else if e.missing() {
+                continue;
+            } else {
+                1.0 - (frame - e.last_seen()) as f32 / ENEMY_MEMORY_FRAMES as f32
+            };
+            if weight <= 0.0 {
+                continue;
+            }
+            let ground = threat(e, false).map(|(range, dpf)| (range, dpf * weight));
+            let air = threat(e, true).map(|(range, dpf)| (range, dpf * weight));
+            if let Some((range, dpf)) = ground {
+                self.ground_threat
+                    .modify_in_range(x, y, tile_radius(range), |i, _, _| i.unwrap_or(0.0) + dpf);
+            }
+            if let Some((range, dpf)) = air {
+                self.air_threat
+                    .modify_in_range(x, y, tile_radius(range), |i, _, _| i.unwrap_or(0.0) + dpf);
+            }
+            if let Some((range, dpf)) = ground
+                .into_iter()
+                .chain(air)
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+            {
+                self.control
+                    .modify_in_range(x, y, tile_radius(range), |i, _, _| i.unwrap_or(0.0) - dpf);
+            }
+        }
+
+        if DRAW_INFLUENCE_MAPS {
+            for e in units.enemy.iter() {
+                let Some((x, y)) = tile_of(e.tile_position()) else {
+                    continue;
+                };
+                let ground_threat = self.ground_threat_at(e.tile_position());
+                cvis().draw_text(
+                    x as i32 * 32,
+                    y as i32 * 32,
+                    format!(
+                        "T: {ground_threat:.2} C: {:.2}",
+                        self.control_at(e.tile_position())
+                    ),
+                );
+            }
+        }
     }
 }
 
@@ -160,4 +323,16 @@ mod tests {
         assert_eq!(grid.get(30, 10), Some(&true));
         assert_eq!(grid.get(31, 10), None);
     }
+
+    #[test]
+    pub fn test_modify_after_reset() {
+        let mut grid = Grid::<u16, 10>::new(0);
+        grid.modify_in_range(5, 5, 1, |i, _, _| i.unwrap_or(0) + 1);
+        grid.modify_in_range(5, 5, 1, |i, _, _| i.unwrap_or(0) + 1);
+        assert_eq!(grid.get(5, 5), Some(&2));
+
+        grid.reset();
+        grid.modify_in_range(5, 5, 1, |i, _, _| i.unwrap_or(0) + 1);
+        assert_eq!(grid.get(5, 5), Some(&1));
+    }
 }
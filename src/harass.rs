@@ -0,0 +1,303 @@
+use crate::boids::*;
+use crate::micro::toward;
+use crate::*;
+
+#[derive(Copy, Clone)]
+pub struct HarassParams {
+    pub unit_type: UnitType,
+    // Below that, the units are left to the squad
+    pub min_units: usize,
+    // Static anti-air we accept at a target base
+    pub max_static_defense: usize,
+}
+
+impl Default for HarassParams {
+    fn default() -> Self {
+        Self {
+            unit_type: UnitType::Zerg_Mutalisk,
+            min_units: 5,
+            max_static_defense: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HarassPhase {
+    Regroup,
+    Attack,
+    Retreat,
+}
+
+impl Default for HarassPhase {
+    fn default() -> Self {
+        Self::Regroup
+    }
+}
+
+#[derive(Default)]
+pub struct Harassment {
+    pub phase: HarassPhase,
+    pub target: Option<TilePosition>,
+    pub phase_start: i32,
+}
+
+impl Harassment {
+    fn switch_to(&mut self, phase: HarassPhase, frame: i32) {
+        if self.phase != phase {
+            cvis().log(|| format!("Harassment: {:?} -> {:?}", self.phase, phase));
+            self.phase = phase;
+            self.phase_start = frame;
+        }
+    }
+}
+
+// Distance of the regroup point to the target worker line
+const REGROUP_DISTANCE: f64 = 12.0 * 32.0;
+// Units further away from the center than this are not considered "stacked"
+const STACK_RADIUS: i32 = 64;
+
+fn is_anti_air(unit: &SUnit) -> bool {
+    !unit.get_type().is_worker()
+        && (unit.get_air_weapon().weapon_type != WeaponType::None
+            || unit.get_type() == UnitType::Terran_Bunker)
+}
+
+fn center_of(units: &[SUnit]) -> Position {
+    let n = units.len().max(1) as i32;
+    Position::new(
+        units.iter().map(|u| u.position().x).sum::<i32>() / n,
+        units.iter().map(|u| u.position().y).sum::<i32>() / n,
+    )
+}
+
+impl MyModule {
+    // Center of the mineral fields around a base, basically where the workers are
+    pub fn worker_line(&self, base: TilePosition) -> Position {
+        let base = base.center();
+        let minerals: Vec<_> = self
+            .units
+            .minerals
+            .iter()
+            .filter(|m| m.position().distance_squared(base) < 320 * 320)
+            .cloned()
+            .collect();
+        if minerals.is_empty() {
+            base
+        } else {
+            (center_of(&minerals) + base) / 2
+        }
+    }
+
+    fn static_anti_air_near(&self, anti_air: &[SUnit], pos: Position) -> usize {
+        anti_air
+            .iter()
+            .filter(|e| {
+                !e.get_type().can_move()
+                    && e.position().distance(pos) as i32 <= e.get_air_weapon().max_range + 96
+            })
+            .count()
+    }
+
+    fn harass_target(
+        &self,
+        from: Position,
+        anti_air: &[SUnit],
+        params: &HarassParams,
+    ) -> Option<TilePosition> {
+        self.bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false))
+            .map(|b| {
+                (
+                    b.position,
+                    self.static_anti_air_near(anti_air, self.worker_line(b.position)),
+                )
+            })
+            .filter(|(_, defense)| *defense <= params.max_static_defense)
+            .min_by_key(|(b, defense)| (*defense, b.center().distance(from) as i32))
+            .map(|(b, _)| b)
+    }
+
+    fn regroup_position(&self, target: Position) -> Position {
+        let home = self
+            .forward_base()
+            .map(|b| b.position())
+            .unwrap_or_else(|| target);
+        toward(target, home, REGROUP_DISTANCE as i32)
+    }
+
+    // Let a stack of air units snipe workers at weakly defended bases
+    pub fn perform_harassment(&mut self, params: HarassParams) -> Result<(), FailureReason> {
+        let harassers: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| u.get_type() == params.unit_type)
+            .cloned()
+            .collect();
+        if harassers.len() < params.min_units {
+            self.harassment = Harassment::default();
+            return Err(FailureReason::misc("Not enough harassers"));
+        }
+        let frame = self.game.get_frame_count();
+        let center = center_of(&harassers);
+        let anti_air: Vec<_> = self
+            .units
+            .enemy
+            .iter()
+            .filter(|e| is_anti_air(e) && e.completed())
+            .cloned()
+            .collect();
+
+        // Re-evaluate the target, a worker line might have become defended in the meantime
+        if self.harassment.phase != HarassPhase::Attack || self.harassment.target.is_none() {
+            self.harassment.target = self.harass_target(center, &anti_air, &params);
+        }
+        let Some(target) = self.harassment.target else {
+            self.harassment = Harassment::default();
+            return Err(FailureReason::misc("No target to harass"));
+        };
+        for h in harassers.iter() {
+            self.tracker.reserve_unit(h);
+        }
+        let worker_line = self.worker_line(target);
+        let regroup_position = self.regroup_position(worker_line);
+        cvis().draw_circle(worker_line.x, worker_line.y, 48, Color::Purple);
+        cvis().draw_line(
+            center.x,
+            center.y,
+            worker_line.x,
+            worker_line.y,
+            Color::Purple,
+        );
+
+        // Anti-air is "arriving" if it could shoot at the stack very soon
+        let threats: Vec<_> = anti_air
+            .iter()
+            .filter(|e| {
+                let reach = e.get_air_weapon().max_range.max(5 * 32)
+                    + if e.get_type().can_move() {
+                        (e.top_speed() * 24.0) as i32
+                    } else {
+                        32
+                    };
+                e.position().distance(center) as i32 <= reach
+            })
+            .collect();
+        let threat_value: i32 = threats
+            .iter()
+            .map(|e| {
+                if e.get_type().can_move() {
+                    self.value_of(e.get_type(), false)
+                } else {
+                    // Static defense is positioned to defend, weight it a bit more
+                    2 * self.value_of(e.get_type(), false)
+                }
+            })
+            .sum();
+        let my_value: i32 = harassers
+            .iter()
+            .map(|u| {
+                self.value_of(u.get_type(), true) * u.hit_points() / u.get_type().max_hit_points()
+            })
+            .sum();
+
+        match self.harassment.phase {
+            HarassPhase::Regroup => {
+                let stacked = harassers
+                    .iter()
+                    .all(|u| u.position().distance(center) as i32 <= STACK_RADIUS);
+                if stacked && center.distance(regroup_position) < 160.0 && threats.is_empty() {
+                    self.harassment.switch_to(HarassPhase::Attack, frame);
+                }
+            }
+            HarassPhase::Attack => {
+                if 2 * threat_value > my_value || threats.iter().any(|e| !e.get_type().can_move()) {
+                    self.harassment.switch_to(HarassPhase::Retreat, frame);
+                }
+            }
+            HarassPhase::Retreat => {
+                if threats.is_empty() && frame - self.harassment.phase_start > 48 {
+                    self.harassment.target = None;
+                    self.harassment.switch_to(HarassPhase::Regroup, frame);
+                }
+            }
+        }
+
+        match self.harassment.phase {
+            HarassPhase::Regroup => {
+                for u in harassers.iter() {
+                    if center.distance(regroup_position) < 160.0 {
+                        // Pull everyone to the center to form a stack
+                        u.move_to(center).ok();
+                    } else {
+                        u.move_to(regroup_position + (u.position() - center) / 2)
+                            .ok();
+                    }
+                }
+            }
+            HarassPhase::Retreat => {
+                let home = self
+                    .forward_base()
+                    .map(|b| b.position())
+                    .unwrap_or(regroup_position);
+                for u in harassers.iter() {
+                    self.flee(u, home);
+                }
+            }
+            HarassPhase::Attack => {
+                let laggards: Vec<_> = harassers
+                    .iter()
+                    .filter(|u| u.position().distance(center) as i32 > 2 * STACK_RADIUS)
+                    .collect();
+                // Don't go in with half the stack
+                if !laggards.is_empty() {
+                    for u in harassers.iter() {
+                        u.move_to(center).ok();
+                    }
+                    return Ok(());
+                }
+                let victim = self
+                    .units
+                    .enemy
+                    .iter()
+                    .filter(|e| {
+                        e.get_type().is_worker()
+                            && e.targetable()
+                            && e.position().distance(center) < 8.0 * 32.0
+                            && self.static_anti_air_near(&anti_air, e.position()) == 0
+                    })
+                    .min_by_key(|e| {
+                        (
+                            e.hit_points() + e.shields(),
+                            e.position().distance(center) as i32,
+                        )
+                    })
+                    .cloned();
+                if let Some(victim) = victim {
+                    cvis().draw_circle(victim.position().x, victim.position().y, 16, Color::Red);
+                    for u in harassers.iter() {
+                        // All shots on one worker, mutas bounce to the others anyways
+                        if u.cooldown() > 0 && u.is_in_weapon_range(&victim) {
+                            let mut forces = vec![
+                                avoid(u, victim.position(), 64.0, 1.0),
+                                goal(u, center, 32.0, 0.5),
+                            ];
+                            forces.extend(self.hazard_forces(u));
+                            let pos = self.positioning(u, &forces).unwrap_or(center);
+                            u.move_to(pos).ok();
+                        } else {
+                            u.attack(&victim).ok();
+                        }
+                    }
+                } else {
+                    for u in harassers.iter() {
+                        // Everyone moves by the same offset to keep the stack together
+                        u.move_to(worker_line + (u.position() - center)).ok();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use crate::boids::*;
+use crate::*;
+
+// Keep this much distance to the edge of a hazard
+const HAZARD_MARGIN: i32 = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HazardKind {
+    Storm,
+    LurkerSpines,
+    SpiderMine,
+    Scarab,
+    Nuke,
+    Irradiate,
+    Plague,
+}
+
+#[derive(Debug)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    pub position: Position,
+    pub radius: i32,
+    // The unit causing the hazard (ie. an irradiated unit) doesn't need to avoid it
+    pub source: Option<SUnit>,
+}
+
+impl Hazard {
+    pub fn affects(&self, unit: &SUnit) -> bool {
+        if self.source.as_ref() == Some(unit) || unit.get_type().is_building() {
+            return false;
+        }
+        match self.kind {
+            HazardKind::Storm | HazardKind::Nuke | HazardKind::Plague => true,
+            HazardKind::LurkerSpines | HazardKind::SpiderMine | HazardKind::Scarab => {
+                !unit.flying()
+            }
+            HazardKind::Irradiate => unit.get_type().is_organic(),
+        }
+    }
+
+    pub fn contains(&self, unit: &SUnit) -> bool {
+        unit.position().distance(self.position) < self.radius as f64
+    }
+}
+
+#[derive(Default)]
+pub struct Hazards {
+    pub zones: Vec<Hazard>,
+}
+
+impl MyModule {
+    pub fn update_hazards(&mut self) {
+        let mut zones = vec![];
+        for b in self.game.get_bullets().iter() {
+            let Some(position) = b.get_position() else {
+                continue;
+            };
+            match b.get_type() {
+                BulletType::Psionic_Storm => zones.push(Hazard {
+                    kind: HazardKind::Storm,
+                    position,
+                    radius: 64,
+                    source: None,
+                }),
+                BulletType::Plague_Cloud => zones.push(Hazard {
+                    kind: HazardKind::Plague,
+                    position,
+                    radius: 64,
+                    source: None,
+                }),
+                // We might not even see the lurker, but its spines give it away
+                BulletType::Subterranean_Spines => zones.push(Hazard {
+                    kind: HazardKind::LurkerSpines,
+                    position: b.get_source().map(|s| s.get_position()).unwrap_or(position),
+                    radius: UnitType::Zerg_Lurker.ground_weapon().max_range() + 32,
+                    source: None,
+                }),
+                _ => (),
+            }
+        }
+        for e in self.units.enemy.iter().filter(|e| e.exists()) {
+            match e.get_type() {
+                UnitType::Zerg_Lurker if e.burrowed() && !e.detected() => zones.push(Hazard {
+                    kind: HazardKind::LurkerSpines,
+                    position: e.position(),
+                    radius: e.get_ground_weapon().max_range + 32,
+                    source: None,
+                }),
+                UnitType::Terran_Vulture_Spider_Mine if !e.burrowed() => zones.push(Hazard {
+                    kind: HazardKind::SpiderMine,
+                    position: e.position(),
+                    radius: 100,
+                    source: None,
+                }),
+                UnitType::Protoss_Scarab => zones.push(Hazard {
+                    kind: HazardKind::Scarab,
+                    position: e
+                        .get_order_target()
+                        .map(|t| t.position())
+                        .unwrap_or_else(|| e.position()),
+                    radius: 64,
+                    source: None,
+                }),
+                UnitType::Terran_Ghost
+                    if matches!(e.get_order(), Order::NukePaint | Order::NukeTrack) =>
+                {
+                    if let Some(position) = e.order_target_position() {
+                        zones.push(Hazard {
+                            kind: HazardKind::Nuke,
+                            position,
+                            radius: 192,
+                            source: None,
+                        })
+                    }
+                }
+                _ => (),
+            }
+        }
+        for u in self
+            .units
+            .mine_all
+            .iter()
+            .filter(|u| u.irradiate_timer() > 0)
+        {
+            zones.push(Hazard {
+                kind: HazardKind::Irradiate,
+                position: u.position(),
+                radius: 64,
+                source: Some(u.clone()),
+            });
+        }
+        for z in zones.iter() {
+            cvis().draw_circle(z.position.x, z.position.y, z.radius, Color::Orange);
+            cvis().draw_text(z.position.x, z.position.y, format!("{:?}", z.kind));
+        }
+        self.hazards.zones = zones;
+    }
+
+    // Forces pushing a unit out of (or keeping it away from) all hazards affecting it
+    pub fn hazard_forces(&self, unit: &SUnit) -> Vec<WeightedPosition> {
+        self.hazards
+            .zones
+            .iter()
+            .filter(|z| z.affects(unit))
+            .map(|z| avoid(unit, z.position, (z.radius + HAZARD_MARGIN) as f32, 2.0))
+            .collect()
+    }
+
+    // Where to go to get to `waypoint` without walking through hazards
+    pub fn hazard_free_waypoint(&self, unit: &SUnit, waypoint: Position) -> Position {
+        let mut forces = self.hazard_forces(unit);
+        if forces.iter().all(|f| f.weight <= 0.0) {
+            return waypoint;
+        }
+        forces.push(goal(unit, waypoint, 0.0, 1.0));
+        self.positioning(unit, &forces).unwrap_or(waypoint)
+    }
+
+    // Move units out of hazards, this takes precedence over anything else the unit might do
+    pub fn avoid_hazards(&mut self) {
+        let endangered: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| {
+                u.get_type().can_move()
+                    && self
+                        .hazards
+                        .zones
+                        .iter()
+                        .any(|z| z.affects(u) && z.contains(u))
+            })
+            .cloned()
+            .collect();
+        for unit in endangered {
+            if self.tracker.try_reserve_unit(&unit).is_none() {
+                continue;
+            }
+            let mut forces = self.hazard_forces(&unit);
+            if !unit.flying() {
+                forces.push(climb(self, &unit, 32, 32, 1.0));
+            }
+            let target = self
+                .positioning(&unit, &forces)
+                .unwrap_or_else(|| unit.position());
+            cvis().log_unit_frame(&unit, || "Avoiding hazard");
+            unit.move_to(target).ok();
+        }
+    }
+}
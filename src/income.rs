@@ -0,0 +1,239 @@
+use crate::*;
+use ahash::AHashMap;
+use std::cell::RefCell;
+
+// https://liquipedia.net/starcraft/Mining
+const MINERALS_PER_TRIP: i32 = 8;
+const GAS_PER_TRIP: i32 = 8;
+// Depleted geysers still yield a little
+const DEPLETED_GAS_PER_TRIP: i32 = 2;
+const MINING_FRAMES: f64 = 80.0;
+const GAS_HARVEST_FRAMES: f64 = 37.0;
+// Accelerating, turning and returning cargo. With average patch distance this results in
+// ~170 frames per trip.
+const TRIP_OVERHEAD_FRAMES: f64 = 45.0;
+const WORKER_SPEED: f64 = 4.49;
+// Resources further away from our depot are not part of its mineral line
+const BASE_RESOURCE_RADIUS: f64 = 10.0 * 32.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    Minerals,
+    Gas,
+}
+
+// A mineral patch or refinery, and the workers gathering from it
+#[derive(Copy, Clone, Debug)]
+pub struct ResourceIncome {
+    pub kind: ResourceKind,
+    // Walking distance to the depot the workers return cargo to, from edge to edge
+    pub distance: f64,
+    pub workers: i32,
+    pub remaining: i32,
+}
+
+impl ResourceIncome {
+    fn frames_per_trip(&self) -> f64 {
+        let harvest = match self.kind {
+            ResourceKind::Minerals => MINING_FRAMES,
+            ResourceKind::Gas => GAS_HARVEST_FRAMES,
+        };
+        harvest + 2.0 * self.distance / WORKER_SPEED + TRIP_OVERHEAD_FRAMES
+    }
+
+    // Resources per frame. Only one worker can harvest at a time, so additional workers add less
+    // and less.
+    pub fn rate(&self) -> f64 {
+        let (per_trip, harvest) = match self.kind {
+            ResourceKind::Minerals if self.remaining <= 0 => return 0.0,
+            ResourceKind::Minerals => (MINERALS_PER_TRIP, MINING_FRAMES),
+            ResourceKind::Gas if self.remaining <= 0 => (DEPLETED_GAS_PER_TRIP, GAS_HARVEST_FRAMES),
+            ResourceKind::Gas => (GAS_PER_TRIP, GAS_HARVEST_FRAMES),
+        };
+        let per_trip = per_trip as f64;
+        (self.workers as f64 * per_trip / self.frames_per_trip()).min(per_trip / harvest)
+    }
+
+    pub fn income(&self, frames: i32) -> i32 {
+        let income = (self.rate() * frames as f64) as i32;
+        match self.kind {
+            ResourceKind::Minerals => income.min(self.remaining),
+            ResourceKind::Gas if self.remaining > 0 => income.min(
+                self.remaining
+                    + (frames as f64 * DEPLETED_GAS_PER_TRIP as f64 / self.frames_per_trip())
+                        as i32,
+            ),
+            ResourceKind::Gas => income,
+        }
+    }
+}
+
+// Walking distances from resources to the closest of our depots. Path lookups are too slow to
+// repeat for every resource whenever we estimate income.
+#[derive(Default)]
+pub struct ResourceDistances {
+    // Depots the distances were computed for
+    depots: RefCell<Vec<SUnit>>,
+    // `None` if no depot can be reached
+    distances: RefCell<AHashMap<SUnit, Option<f64>>>,
+}
+
+impl MyModule {
+    // Walking distance from `resource` to the closest depot, from edge to edge
+    fn resource_distance(&self, depots: &[&SUnit], resource: &SUnit) -> Option<f64> {
+        let cache = &self.resource_distances;
+        if cache.depots.borrow().iter().ne(depots.iter().copied()) {
+            *cache.depots.borrow_mut() = depots.iter().map(|&d| d.clone()).collect();
+            cache.distances.borrow_mut().clear();
+        }
+        *cache
+            .distances
+            .borrow_mut()
+            .entry(resource.clone())
+            .or_insert_with(|| {
+                depots
+                    .iter()
+                    .filter(|d| self.game.has_path(d.position(), resource.position()))
+                    .map(|d| {
+                        // Paths are measured between centers, drones walk from edge to edge
+                        let path = self.map.get_path(d.position(), resource.position()).1 as f64;
+                        let centers = d.position().distance(resource.position());
+                        (path - centers + d.distance_to(resource) as f64).max(0.0)
+                    })
+                    .min_by_key(|d| OrderedFloat(*d))
+            })
+    }
+
+    // All resources we are gathering from (or could gather from at our bases)
+    pub fn resource_incomes(&self) -> Vec<ResourceIncome> {
+        let depots: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| u.get_type().is_resource_depot())
+            .collect();
+        let workers: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| u.get_type().is_worker() && (u.gathering_minerals() || u.gathering_gas()))
+            .collect();
+        let gathered_from = |resource: &SUnit| {
+            workers
+                .iter()
+                .filter(|w| {
+                    w.get_order_target().as_ref() == Some(resource)
+                        || w.target().as_ref() == Some(resource)
+                })
+                .count() as i32
+        };
+        self.units
+            .all()
+            .filter(|u| {
+                u.get_type().is_mineral_field()
+                    || u.get_type().is_refinery() && u.player().is_me() && u.completed()
+            })
+            .filter_map(|resource| {
+                let workers = gathered_from(resource);
+                let near_depot = depots
+                    .iter()
+                    .any(|d| d.position().distance(resource.position()) < BASE_RESOURCE_RADIUS);
+                if workers == 0 && !near_depot {
+                    return None;
+                }
+                // Long distance mining: The workers have to walk all the way back
+                let distance = self.resource_distance(&depots, resource)?;
+                Some(ResourceIncome {
+                    kind: if resource.get_type().is_mineral_field() {
+                        ResourceKind::Minerals
+                    } else {
+                        ResourceKind::Gas
+                    },
+                    distance,
+                    workers,
+                    remaining: resource.resources(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn estimate_gms(&self, frames: i32, sub_workers: i32) -> Gms {
+        let mut incomes = self.resource_incomes();
+        // Workers taken away (ie. to build something) are taken from where they add the least
+        for _ in 0..sub_workers {
+            if let Some(income) = incomes
+                .iter_mut()
+                .filter(|i| i.kind == ResourceKind::Minerals && i.workers > 0)
+                .max_by_key(|i| i.workers)
+            {
+                income.workers -= 1;
+            }
+        }
+        let income = |kind| {
+            incomes
+                .iter()
+                .filter(|i| i.kind == kind)
+                .map(|i| i.income(frames))
+                .sum()
+        };
+        Gms {
+            minerals: income(ResourceKind::Minerals),
+            gas: income(ResourceKind::Gas),
+            supply: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(distance: f64, workers: i32) -> ResourceIncome {
+        ResourceIncome {
+            kind: ResourceKind::Minerals,
+            distance,
+            workers,
+            remaining: 1500,
+        }
+    }
+
+    #[test]
+    fn additional_workers_should_add_less() {
+        let one = patch(96.0, 1).rate();
+        let two = patch(96.0, 2).rate();
+        let three = patch(96.0, 3).rate();
+        let four = patch(96.0, 4).rate();
+
+        assert!(two - one >= three - two);
+        assert!(three - two > 0.0);
+        assert_eq!(four, three);
+    }
+
+    #[test]
+    fn long_distance_mining_should_yield_less() {
+        assert!(patch(20.0 * 32.0, 2).rate() < patch(96.0, 2).rate() / 2.0);
+    }
+
+    #[test]
+    fn depleted_resources_should_yield_nothing_or_less() {
+        let mut mined_out = patch(96.0, 2);
+        mined_out.remaining = 20;
+        let geyser = ResourceIncome {
+            kind: ResourceKind::Gas,
+            distance: 96.0,
+            workers: 3,
+            remaining: 0,
+        };
+
+        assert_eq!(mined_out.income(24 * 60), 20);
+        assert!(
+            geyser.rate()
+                < ResourceIncome {
+                    remaining: 5000,
+                    ..geyser
+                }
+                .rate()
+                    / 3.0
+        );
+    }
+}
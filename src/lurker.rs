@@ -0,0 +1,151 @@
+use crate::micro::toward;
+use crate::*;
+
+// Lurkers sharing one choke
+const LURKERS_PER_POSITION: usize = 2;
+// Offsets of lurkers sharing a position, spines hit in a line so don't stack them
+const SLOT_OFFSETS: [(i32, i32); LURKERS_PER_POSITION] = [(0, 0), (48, 48)];
+// A position where one of our lurkers was detected is avoided for this long
+const COMPROMISED_FRAMES: i32 = 24 * 45;
+
+#[derive(Default)]
+pub struct Lurkers {
+    compromised: Vec<(Position, i32)>,
+}
+
+impl MyModule {
+    pub fn detected_by_enemy(&self, unit: &SUnit) -> bool {
+        self.units.enemy.iter().any(|e| {
+            e.exists()
+                && e.completed()
+                && e.get_type().is_detector()
+                && e.position().distance(unit.position()) as i32 <= e.get_type().sight_range() + 32
+        })
+    }
+
+    // Chokes worth holding: Our own outgoing choke first, then the approaches of enemy bases
+    fn lurker_positions(&self) -> Vec<Position> {
+        let Some(home) = self.forward_base().map(|b| b.position()) else {
+            return vec![];
+        };
+        let enemy_bases: Vec<_> = self
+            .bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false))
+            .map(|b| b.position.center())
+            .collect();
+        let mut result = vec![];
+        if let Some(enemy) = enemy_bases.first() {
+            if let Some(cp) = self.map.get_path(home, *enemy).0.first() {
+                // Stay a bit behind the choke, they have to walk into the spines
+                result.push(toward(cp.top.center(), home, 64));
+            }
+        }
+        for enemy in enemy_bases.iter() {
+            if let Some(cp) = self.map.get_path(*enemy, home).0.first() {
+                // Outside of their choke, so we don't sit in range of their defense
+                result.push(toward(cp.top.center(), home, 96));
+            }
+        }
+        let frame = self.game.get_frame_count();
+        result.retain(|p| {
+            !self
+                .lurkers
+                .compromised
+                .iter()
+                .any(|(c, f)| frame - f < COMPROMISED_FRAMES && c.distance(*p) < 128.0)
+        });
+        result
+    }
+
+    // Burrow lurkers at chokes and ramps, unburrow and reposition when detected or idle
+    pub fn perform_lurkers(&mut self) {
+        let mut lurkers: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| u.get_type() == UnitType::Zerg_Lurker)
+            .cloned()
+            .collect();
+        if lurkers.is_empty() {
+            return;
+        }
+        let frame = self.game.get_frame_count();
+        self.lurkers
+            .compromised
+            .retain(|(_, f)| frame - f < COMPROMISED_FRAMES);
+        let mut free_slots: Vec<_> = self
+            .lurker_positions()
+            .iter()
+            .flat_map(|p| SLOT_OFFSETS.iter().map(move |o| *p + *o))
+            .collect();
+        let home = self.forward_base().map(|b| b.position());
+        // Burrowed lurkers pick first, so they keep their slots
+        lurkers.sort_by_key(|l| !l.burrowed());
+
+        for lurker in lurkers.iter() {
+            self.tracker.reserve_unit(lurker);
+            let weapon = lurker.get_ground_weapon();
+            let targets = self.units.all_in_range(lurker, weapon.max_range).any(|e| {
+                e.player().is_enemy()
+                    && !e.flying()
+                    && e.targetable()
+                    && !e.get_type().is_building()
+            });
+            if lurker.burrowed()
+                && self.detected_by_enemy(lurker)
+                && self.grids.ground_threat_at(lurker.tile_position()) > 0.0
+            {
+                // They see us and can shoot at us, find a better spot
+                cvis().log_unit_frame(lurker, || "Lurker detected, repositioning");
+                self.lurkers.compromised.push((lurker.position(), frame));
+                lurker.unburrow().ok();
+                continue;
+            }
+            let compromised = self
+                .lurkers
+                .compromised
+                .iter()
+                .any(|(c, _)| c.distance(lurker.position()) < 128.0);
+            if targets && !compromised {
+                if !lurker.burrowed() {
+                    lurker.burrow().ok();
+                }
+                continue;
+            }
+            // Keep the assignment stable: Take the closest free slot
+            let slot = free_slots
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, s)| s.distance(lurker.position()) as i32)
+                .map(|(i, s)| (i, *s));
+            let Some((i, slot)) = slot else {
+                if lurker.burrowed() {
+                    continue;
+                }
+                if let Some(home) = home {
+                    self.safe_move_to(lurker, home);
+                }
+                continue;
+            };
+            free_slots.swap_remove(i);
+            cvis().draw_line(
+                lurker.position().x,
+                lurker.position().y,
+                slot.x,
+                slot.y,
+                Color::Teal,
+            );
+            if lurker.position().distance(slot) < 32.0 {
+                if !lurker.burrowed() {
+                    lurker.burrow().ok();
+                }
+            } else if lurker.burrowed() {
+                cvis().log_unit_frame(lurker, || "Lurker out of targets, repositioning");
+                lurker.unburrow().ok();
+            } else {
+                self.safe_move_to(lurker, slot);
+            }
+        }
+    }
+}
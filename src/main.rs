@@ -1,54 +1,94 @@
 //
+mod base_trade;
 mod boids;
 mod build;
+mod build_order;
+mod burrow;
 mod cherry_vis;
 mod cluster;
 mod combat_sim;
 mod config;
+mod defiler;
 mod duration;
+mod econ_sim;
+mod expansion;
+mod gas;
 mod gathering;
 mod gms;
 mod grid;
+mod harass;
+mod hazard;
+mod income;
+mod lurker;
 mod micro;
+mod overlords;
+mod pathing;
+mod placement;
+mod production;
+mod queen;
+mod runby;
 mod sbase;
 mod scouting;
 mod skirmish;
 mod splayer;
 mod squad;
+mod staging;
 mod strat;
 mod strats;
+mod stutter;
 mod sunit;
 mod targeting;
 mod tracker;
+mod transfer;
 mod train;
 mod upgrade;
+mod wall;
+mod worker_defense;
 
-use boids::{avoid, climb, WeightedPosition};
+use build_order::*;
+use burrow::*;
 use cherry_vis::*;
 use cluster::WithPosition;
 use config::*;
+use defiler::*;
+use expansion::*;
+use gas::*;
 use gathering::*;
 use gms::*;
 use grid::Grids;
+use harass::*;
+use hazard::*;
+use income::*;
 use log::{error, info, warn};
+use lurker::*;
 use metered::{metered, ResponseTime, Throughput};
 use ordered_float::OrderedFloat;
+use overlords::*;
+use pathing::*;
+use placement::*;
+use production::*;
+use queen::*;
+use runby::*;
 use rsbwapi::sma::*;
 use rsbwapi::*;
-use rstar::AABB;
 use sbase::Bases;
 use scouting::*;
 use skirmish::*;
 use splayer::*;
 use squad::*;
+use staging::*;
 use std::borrow::Cow;
 use std::sync::Mutex;
 use strat::*;
+use stutter::*;
 use sunit::*;
 use targeting::*;
 use tracker::*;
+use transfer::*;
 use train::*;
 use upgrade::*;
+use wall::*;
+use worker_defense::*;
 
 lazy_static::lazy_static! {
 
@@ -60,13 +100,28 @@ pub struct Metrics {
     main_metrics: MainMetrics,
     units_metrics: UnitsMetrics,
     skirmishes_metrics: SkirmishesMetrics,
+    micro_metrics: MicroMetrics,
     dbscan: ResponseTime,
 }
 
-#[derive(Default)]
 pub struct AttackParams {
     aggression_value: i32,
     min_army: usize,
+    // Value of units to gather at the staging position before moving out, 0 to not stage at all
+    min_army_value: i32,
+    // Move out anyway after waiting this long at the staging position
+    max_staging_frames: i32,
+}
+
+impl Default for AttackParams {
+    fn default() -> Self {
+        Self {
+            aggression_value: 0,
+            min_army: 0,
+            min_army_value: 0,
+            max_staging_frames: 45 * 24,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -84,15 +139,34 @@ impl FailureReason {
 
 pub struct MyModule {
     pub game: Game,
+    pub gas: GasController,
     pub units: Units,
     pub bases: Bases,
+    pub burrows: Burrows,
     pub skirmishes: Skirmishes,
+    pub staging: Staging,
     pub players: Players,
     pub tracker: Tracker,
+    pub transfers: WorkerTransfers,
     pub grids: Grids,
+    pub harassment: Harassment,
+    pub hazards: Hazards,
+    pub lurkers: Lurkers,
     pub map: Map,
+    pub mining: Mining,
+    pub overlords: Overlords,
+    pub pathing: Pathing,
+    pub queue: ProductionQueue,
+    pub projected_damage: ProjectedDamage,
+    pub resource_distances: ResourceDistances,
+    pub runby: Runby,
     pub strat: std::rc::Rc<Strategy>,
     pub strategy_records: Vec<StrategyRecord>,
+    pub stutter: Stutter,
+    // Unit of our army closest to the attack target
+    pub vanguard: Option<SUnit>,
+    pub wall: Wall,
+    pub worker_defense: WorkerDefense,
 }
 
 impl MyModule {
@@ -224,7 +298,10 @@ impl MyModule {
     pub fn ensure_free_supply(&mut self, amount: i32) {
         let supply_delta = self.get_pending_supply() / 2;
         if supply_delta < amount {
-            self.start_train(TrainParam::train(UnitType::Zerg_Overlord));
+            self.with_priority(SUPPLY_PRIORITY, |module| {
+                module.start_train(TrainParam::train(UnitType::Zerg_Overlord))
+            })
+            .ok();
         }
     }
 
@@ -324,32 +401,44 @@ impl MyModule {
         }
         self.ensure_base_count(2);
         self.ensure_building_count(UnitType::Zerg_Spawning_Pool, 1);
+        if self.enemy_race() == Some(Race::Protoss) {
+            self.with_priority(DEFENSE_PRIORITY, |module| module.ensure_zvp_wall())
+                .ok();
+        }
         if self.count_pending_or_ready(|ut| ut.is_successor_of(UnitType::Zerg_Hatchery)) < 3 {
             self.ensure_unit_count(UnitType::Zerg_Drone, 13);
         }
-        self.ensure_base_count(3);
+        self.with_deadline(24 * 60 * 3, |module| module.ensure_base_count(3))
+            .ok();
         self.ensure_unit_count(UnitType::Zerg_Zergling, 6);
-        self.ensure_building_count(
-            UnitType::Zerg_Creep_Colony,
-            1_usize.min(2_usize.saturating_sub(
-                self.count_pending_or_ready(|ut| ut == UnitType::Zerg_Sunken_Colony),
-            )),
-        );
-        self.ensure_building_count(UnitType::Zerg_Sunken_Colony, 2);
+        self.with_priority(DEFENSE_PRIORITY, |module| {
+            module.ensure_building_count(
+                UnitType::Zerg_Creep_Colony,
+                1_usize.min(2_usize.saturating_sub(
+                    module.count_pending_or_ready(|ut| ut == UnitType::Zerg_Sunken_Colony),
+                )),
+            );
+            module.ensure_building_count(UnitType::Zerg_Sunken_Colony, 2);
+        });
         self.ensure_unit_count(UnitType::Zerg_Drone, 13);
         self.ensure_building_count(UnitType::Zerg_Extractor, 1);
         self.ensure_unit_count(UnitType::Zerg_Overlord, 3);
-        self.ensure_building_count(UnitType::Zerg_Lair, 1);
-        self.ensure_building_count(UnitType::Zerg_Spire, 1);
+        self.with_priority(TECH_PRIORITY, |module| {
+            module.with_deadline(24 * 60 * 4, |module| {
+                module.ensure_building_count(UnitType::Zerg_Lair, 1)
+            });
+            module.with_deadline(24 * 60 * 6, |module| {
+                module.ensure_building_count(UnitType::Zerg_Spire, 1)
+            });
+        });
         self.ensure_building_count(UnitType::Zerg_Hatchery, 4);
         self.ensure_unit_count(UnitType::Zerg_Overlord, 5);
         self.ensure_free_supply(4);
         self.pump(UnitType::Zerg_Mutalisk);
 
-        self.ensure_gathering_gas(GatherParams {
-            ..Default::default()
-        });
+        self.ensure_gathering_gas(GatherParams::default());
 
+        self.perform_harassment(HarassParams::default()).ok();
         self.perform_attacking(AttackParams::default());
         self.perform_scouting(ScoutParams {
             max_workers: if self.game.self_().unwrap().supply_used() > 10 * 2 {
@@ -440,7 +529,6 @@ impl MyModule {
         self.ensure_free_supply(2);
         self.ensure_unit_count(UnitType::Zerg_Zergling, 100);
         self.ensure_gathering_gas(GatherParams {
-            required_resources: -self.tracker.available_gms.gas,
             max_workers: 3,
             ..Default::default()
         });
@@ -500,35 +588,18 @@ impl MyModule {
             .cloned()
             .collect();
 
-        // Avoid storms
-        for pos in self
-            .game
-            .get_bullets()
+        // Casters position themselves relative to the vanguard, so pick it before the squad moves
+        self.vanguard = attackers
             .iter()
-            .filter(|b| b.get_type() == BulletType::Psionic_Storm)
-            .flat_map(|b| b.get_position())
-        {
-            for unit in self
-                .units
-                .all_in_envelope(AABB::from_corners(
-                    [pos.x - 32, pos.y - 32],
-                    [pos.x + 32, pos.y + 32],
-                ))
-                .filter(|u| u.player().is_me() && u.get_type().can_move())
-            {
-                let avoiding = avoid(unit, pos, 32.0 * 1.42, 1.0);
-                let forces = if unit.flying() {
-                    [avoiding, WeightedPosition::ZERO]
-                } else {
-                    [avoiding, climb(self, &unit, 32, 32, 1.0)]
-                };
-                let target = self
-                    .positioning(&unit, &forces)
-                    .unwrap_or_else(|| unit.position());
-                unit.move_to(target);
-                self.tracker.reserve_unit(unit);
-            }
-        }
+            .filter(|u| is_attacker(u))
+            .min_by_key(|u| self.map.get_path(u.position(), target).1)
+            .cloned();
+        // Lurkers hold their positions instead of joining the squad
+        self.perform_lurkers();
+        self.perform_defilers();
+        self.perform_queens();
+        self.perform_burrow();
+        self.perform_staging(&attack_params, target);
 
         if !attackers.is_empty() {
             Squad {
@@ -586,18 +657,26 @@ impl MyModule {
         self.ensure_building_count(UnitType::Zerg_Evolution_Chamber, 1);
         self.ensure_upgrade(UpgradeType::Zerg_Carapace, 1);
         self.ensure_upgrade(UpgradeType::Zerg_Missile_Attacks, 1);
+        if self.count_completed(|ut| ut == UnitType::Zerg_Hydralisk) >= 8 {
+            self.ensure_building_count(UnitType::Zerg_Lair, 1);
+            self.ensure_tech(TechType::Lurker_Aspect);
+            self.ensure_unit_count(UnitType::Zerg_Lurker, 4);
+        }
+        if self.count_completed(|ut| ut == UnitType::Zerg_Lurker) >= 4 {
+            self.ensure_building_count(UnitType::Zerg_Queens_Nest, 1);
+            self.ensure_building_count(UnitType::Zerg_Hive, 1);
+            self.ensure_building_count(UnitType::Zerg_Defiler_Mound, 1);
+            self.ensure_tech(TechType::Consume);
+            self.ensure_tech(TechType::Plague);
+            self.ensure_unit_count(UnitType::Zerg_Defiler, 2);
+        }
         self.ensure_gathering_gas(GatherParams {
-            max_workers: 0.max(3 - self.game.self_().unwrap().gas() / 200),
-            // Researched grooved spines? Full gathering
-            required_resources: if self.has_pending_or_upgraded(UpgradeType::Grooved_Spines, 1) {
-                999
-            } else {
-                0.max(UpgradeType::Grooved_Spines.gas_price(1) - self.game.self_().unwrap().gas())
-            },
+            max_workers: 3,
             ..Default::default()
         });
         self.perform_attacking(AttackParams {
             min_army: 12,
+            min_army_value: 1300,
             ..Default::default()
         });
         self.perform_scouting(ScoutParams {
@@ -670,17 +749,22 @@ impl MyModule {
         self.ensure_unit_count(UnitType::Zerg_Overlord, 3);
         self.ensure_unit_count(UnitType::Zerg_Zergling, 12);
         self.ensure_upgrade(UpgradeType::Metabolic_Boost, 1);
+        if self.has_pending_or_upgraded(UpgradeType::Metabolic_Boost, 1) {
+            self.ensure_tech(TechType::Burrowing);
+        }
         self.ensure_free_supply(4);
         self.pump(UnitType::Zerg_Zergling);
 
         self.ensure_gathering_gas(GatherParams {
-            required_resources: -self.tracker.available_gms.gas,
             max_workers: 3,
             ..Default::default()
         });
 
+        // Lings running past their defense are not available to the squad
+        self.perform_runby(RunbyParams::default()).ok();
         self.perform_attacking(AttackParams {
             min_army: 28,
+            min_army_value: 700,
             ..Default::default()
         });
         self.perform_scouting(ScoutParams {
@@ -766,7 +850,7 @@ impl AiModule for MyModule {
         self.map = Map::new(game);
         self.bases = Bases::new(self);
 
-        let strategies = [
+        let mut strategies = vec![
             // Strategy::from_fn(&Self::three_hatch_zergling),
             // Strategy::from_fn(&Self::two_hatch_hydra),
             Strategy::from_fn(&Self::three_hatch_spire),
@@ -774,6 +858,11 @@ impl AiModule for MyModule {
             // Strategy::from_fn(&Self::four_pool_aggressive),
             // Strategy::from_fn(&Self::opening_styx),
         ];
+        strategies.extend(
+            load_build_orders()
+                .into_iter()
+                .map(Strategy::from_build_order),
+        );
         let time = std::time::SystemTime::now()
             .duration_since(std::time::SystemTime::UNIX_EPOCH)
             .unwrap()
@@ -888,9 +977,13 @@ impl AiModule for MyModule {
             let me = self.game.self_().unwrap();
             self.players.update(&self.game);
             self.units.update(&self.game, &self.players);
-            self.grids.update(&self.units);
+            self.grids.update(self.game.get_frame_count(), &self.units);
+            self.pathing.update(self.game.get_frame_count());
+            self.projected_damage.update(self.game.get_frame_count());
+            self.stutter.update(self.game.get_frame_count(), &self.units);
             self.bases.update(&self.game, &self.units);
             self.skirmishes = Skirmishes::new(self, &self.units.clusters);
+            self.vanguard = None;
             self.tracker.unrealized.clear();
             self.tracker.available_units = self
                 .units
@@ -912,6 +1005,8 @@ impl AiModule for MyModule {
                 .filter(|u| u.build_type() != u.get_type() && u.build_type().is_building())
                 .map(|u| u.build_type().price())
                 .sum();
+            // Money for queued items is reserved before the strategy gets to spend it
+            self.update_production_queue();
             //     let self_ = game.self_().unwrap();
             //
             for b in self
@@ -981,8 +1076,23 @@ impl AiModule for MyModule {
                 }
             }
 
+            self.update_hazards();
+            self.avoid_hazards();
+
+            // Pull drones before the strategy gets to use them for building
+            self.perform_worker_defense();
+
             self.strat.clone().tick(self).ok();
 
+            // Overlords not used for scouting spread out for vision
+            self.perform_overlords();
+
+            // Drones on gas depend on what the strategy is planning to spend
+            self.perform_gas_gathering();
+
+            // Move drones away from mined out or over saturated bases
+            self.perform_worker_transfers();
+
             // Always gather minerals with the remaining drones, can't imagine a situation where
             // this is a bad idea...
             self.ensure_gathering_minerals();
@@ -1013,15 +1123,33 @@ fn main() {
 
     rsbwapi::start(|game| MyModule {
         game: game.clone(),
+        gas: GasController::default(),
         bases: Bases::default(),
+        burrows: Burrows::default(),
         units: Default::default(),
         players: Default::default(),
         tracker: Tracker::default(),
+        transfers: WorkerTransfers::default(),
         map: Map::new(game),
+        mining: Mining::default(),
+        pathing: Pathing::default(),
+        queue: ProductionQueue::default(),
+        projected_damage: ProjectedDamage::default(),
+        resource_distances: ResourceDistances::default(),
+        runby: Runby::default(),
         skirmishes: Default::default(),
+        staging: Staging::default(),
         strat: std::rc::Rc::new(Strategy::from_fn(&MyModule::two_hatch_hydra)),
         grids: Grids::new(),
+        harassment: Harassment::default(),
+        hazards: Hazards::default(),
+        lurkers: Lurkers::default(),
+        overlords: Overlords::default(),
         strategy_records: vec![],
+        stutter: Stutter::default(),
+        vanguard: None,
+        wall: Wall::default(),
+        worker_defense: WorkerDefense::default(),
     });
     // if let Ok(report) = guard.report().build() {
     //     let file = std::fs::File::create("flamegraph.svg").unwrap();
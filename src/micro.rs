@@ -1,9 +1,19 @@
 use crate::cherry_vis::*;
 use crate::cluster::WithPosition;
-use crate::{boids::*, MyModule, SUnit};
+use crate::{boids::*, MyModule, SUnit, StutterAction};
 use rsbwapi::{Position, UnitType};
 use rstar::AABB;
 
+// Position `distance` pixels from `from` in the direction of `to`
+pub fn toward(from: Position, to: Position, distance: i32) -> Position {
+    let len = from.distance(to) as i32;
+    if len <= distance {
+        to
+    } else {
+        from + (to - from) * distance / len
+    }
+}
+
 impl MyModule {
     pub fn flee(&self, unit: &SUnit, toward: Position) {
         let pos = unit.position();
@@ -28,7 +38,8 @@ impl MyModule {
         for boid in boid_forces.iter_mut() {
             boid.weight /= amount;
         }
-        boid_forces.push(follow_path(self, unit, toward, 0.2));
+        boid_forces.push(follow_safe_path(self, unit, toward, 0.2));
+        boid_forces.extend(self.hazard_forces(unit));
         let target = self
             .positioning(&unit, &boid_forces)
             .unwrap_or_else(|| toward);
@@ -44,16 +55,20 @@ impl MyModule {
             && (enemy_weapon.cooldown >= my_weapon.cooldown
                 || enemy.top_speed() <= unit.top_speed() && enemy.top_speed() > 0.5);
         let enemy_has_targeted_us = enemy.get_order_target().as_ref() == Some(unit);
+        let action = self.stutter_action(unit);
+        if action == StutterAction::Wait {
+            cvis().log_unit_frame(unit, || "Attack animation");
+            return;
+        }
         // Kite if we have time and ability
-        // TODO check whether we can turn away and back again in time
-        let kite = unit.cooldown() > 2 + unit.frames_to_turn_180()
-            && !unit.sleeping()
+        let kite = action == StutterAction::Move
         // If the enemy is targeting me, kiting should give allies more time to help
             && (longer_range_and_not_slower || enemy_has_targeted_us);
         if kite {
             cvis().log_unit_frame(unit, || format!("Kiting CD: {}", unit.cooldown()));
             let pos = unit.position();
             let mut boid_forces = vec![separation(&unit, enemy, my_weapon.max_range as f32, 1.0)];
+            boid_forces.extend(self.hazard_forces(unit));
             if !unit.flying() {
                 // boid_forces.push(climb(self, &unit, 32, 32, 1.0));
                 boid_forces.push(follow_path(self, &unit, enemy.position(), 2.0));
@@ -74,9 +89,23 @@ impl MyModule {
             unit.move_to(target).ok();
             return;
         }
-        let wpn = unit.weapon_against(enemy);
-        let distance = unit.distance_to(enemy).saturating_sub(wpn.max_range);
         // TODO lead/path enemy
-        unit.attack(enemy).ok();
+        self.stutter_attack(unit, enemy);
+    }
+
+    // Keep support units (casters, detectors) some distance behind the vanguard of our army
+    pub fn stay_behind_vanguard(&self, unit: &SUnit, distance: i32) {
+        let Some(home) = self.forward_base().map(|b| b.position()) else {
+            return;
+        };
+        if self.grids.threat_at(unit.position(), unit.flying()) > 0.0 {
+            self.flee(unit, home);
+            return;
+        }
+        let Some(vanguard) = self.vanguard.as_ref().map(|v| v.position()) else {
+            self.safe_move_to(unit, home);
+            return;
+        };
+        self.safe_move_to(unit, toward(vanguard, home, distance));
     }
 }
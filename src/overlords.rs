@@ -0,0 +1,166 @@
+use crate::*;
+use ahash::AHashMap;
+
+// Enemy anti-air closer than this makes overlords fall back to spore cover
+const ANTI_AIR_ALERT_DISTANCE: f64 = 8.0 * 32.0;
+// Detectors escorting the army
+const MAX_ESCORTS: usize = 2;
+const ESCORT_DISTANCE_TO_VANGUARD: i32 = 2 * 32;
+
+#[derive(Default)]
+pub struct Overlords {
+    // Vision spot each overlord was sent to
+    assignments: AHashMap<UnitId, Position>,
+}
+
+impl MyModule {
+    // Places worth watching, most important first
+    fn overlord_spots(&self) -> Vec<Position> {
+        let Some(home) = self.forward_base().map(|b| b.position()) else {
+            return vec![];
+        };
+        let enemy_main = self
+            .bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false))
+            .map(|b| b.position.center())
+            .next()
+            .or_else(|| {
+                self.game
+                    .get_start_locations()
+                    .iter()
+                    .map(|l| l.center())
+                    .max_by_key(|l| l.distance(home) as i32)
+            });
+        let mut spots = vec![];
+        // The approach to our natural and the chokes the enemy will come through
+        if let Some(enemy_main) = enemy_main {
+            spots.extend(
+                self.map
+                    .get_path(home, enemy_main)
+                    .0
+                    .iter()
+                    .map(|cp| cp.top.center()),
+            );
+        }
+        // Expansions we don't own, closest first
+        let mut expansions: Vec<_> = self
+            .bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| !p.is_me()).unwrap_or(true))
+            .map(|b| b.position.center())
+            .collect();
+        expansions.sort_by_key(|p| p.distance(home) as i32);
+        spots.extend(expansions);
+        spots.retain(|p| self.grids.air_threat_at(*p) <= 0.0);
+        spots
+    }
+
+    // Spore colony or base to retreat to
+    fn overlord_shelter(&self, overlord: &SUnit) -> Option<Position> {
+        self.units
+            .my_completed
+            .iter()
+            .filter(|u| u.get_type() == UnitType::Zerg_Spore_Colony)
+            .min_by_key(|u| u.position().distance(overlord.position()) as i32)
+            .map(|u| u.position())
+            .or_else(|| self.main_base().map(|b| b.position()))
+    }
+
+    fn anti_air_close(&self, overlord: &SUnit) -> bool {
+        self.grids.air_threat_at(overlord.position()) > 0.0
+            || self.units.enemy.iter().any(|e| {
+                e.exists()
+                    && e.completed()
+                    && e.get_air_weapon().weapon_type != WeaponType::None
+                    && e.position().distance(overlord.position()) < ANTI_AIR_ALERT_DISTANCE
+            })
+    }
+
+    // Spread overlords over vision spots, keep them safe and escort the army if detection is
+    // required
+    pub fn perform_overlords(&mut self) {
+        let mut overlords: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| u.get_type() == UnitType::Zerg_Overlord)
+            .cloned()
+            .collect();
+        for o in overlords.iter() {
+            self.tracker.reserve_unit(o);
+        }
+        self.overlords
+            .assignments
+            .retain(|id, _| overlords.iter().any(|o| o.id() == *id));
+
+        // Overlords in danger retreat first
+        let (endangered, safe): (Vec<_>, Vec<_>) =
+            overlords.drain(..).partition(|o| self.anti_air_close(o));
+        for o in endangered.iter() {
+            self.overlords.assignments.remove(&o.id());
+            if let Some(shelter) = self.overlord_shelter(o) {
+                cvis().log_unit_frame(o, || "Overlord retreating");
+                self.flee(o, shelter);
+            }
+        }
+        let mut overlords = safe;
+
+        let cloaked_enemies = self.units.enemy.iter().any(|e| {
+            !e.missing()
+                && (e.cloaked()
+                    || e.burrowed()
+                    || e.get_type().has_permanent_cloak()
+                    || e.get_type() == UnitType::Zerg_Lurker)
+        });
+        if cloaked_enemies {
+            if let Some(vanguard) = self.vanguard.as_ref().map(|v| v.position()) {
+                overlords.sort_by_key(|o| o.position().distance(vanguard) as i32);
+                for o in overlords.drain(..MAX_ESCORTS.min(overlords.len())) {
+                    self.overlords.assignments.remove(&o.id());
+                    cvis().log_unit_frame(&o, || "Escorting army");
+                    self.stay_behind_vanguard(&o, ESCORT_DISTANCE_TO_VANGUARD);
+                }
+            }
+        }
+
+        let mut spots = self.overlord_spots();
+        // Keep existing assignments if the spot is still valid
+        let mut unassigned = vec![];
+        for o in overlords {
+            let assigned = self.overlords.assignments.get(&o.id()).copied();
+            match assigned.and_then(|spot| spots.iter().position(|s| *s == spot)) {
+                Some(i) => {
+                    let spot = spots.remove(i);
+                    self.safe_move_to(&o, spot);
+                }
+                None => {
+                    self.overlords.assignments.remove(&o.id());
+                    unassigned.push(o);
+                }
+            }
+        }
+        let mut overlords = unassigned;
+        for spot in spots {
+            let Some(i) = overlords
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, o)| o.position().distance(spot) as i32)
+                .map(|(i, _)| i)
+            else {
+                break;
+            };
+            let o = overlords.swap_remove(i);
+            self.overlords.assignments.insert(o.id(), spot);
+            self.safe_move_to(&o, spot);
+        }
+        // Nothing to watch, the bulk stays at home
+        for o in overlords.iter() {
+            if let Some(shelter) = self.overlord_shelter(o) {
+                if o.position().distance(shelter) > 96.0 {
+                    self.safe_move_to(o, shelter);
+                }
+            }
+        }
+    }
+}
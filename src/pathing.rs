@@ -0,0 +1,362 @@
+use crate::*;
+use ahash::AHashMap;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+
+// Cost of a straight/diagonal step to the next tile
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+// Cost added per damage per frame enemies can deal on a tile, a tile in range of one cannon is
+// worth a detour of about 5 tiles
+const THREAT_PENALTY: f32 = 60.0;
+// Flow fields are recomputed after this many frames, threats move around
+const FLOW_FIELD_TTL: i32 = 12;
+// Tiles to look ahead when asking for the next waypoint
+const WAYPOINT_LOOKAHEAD: usize = 4;
+// Goals within the last area of a path are snapped to cells of this many tiles, so units going
+// to about the same place share a flow field
+const GOAL_CELL_SIZE: i32 = 4;
+// Flow fields for waypoints only cover tiles this close to their goal, the goal is at most the
+// next choke away
+const WAYPOINT_FIELD_RADIUS: i32 = 48;
+// Tiles to search around a goal for a passable one
+const GOAL_SNAP_RADIUS: i32 = 4;
+
+const UNREACHABLE: u32 = u32::MAX;
+
+const NEIGHBORS: [(i32, i32, u32); 8] = [
+    (-1, 0, STRAIGHT_COST),
+    (1, 0, STRAIGHT_COST),
+    (0, -1, STRAIGHT_COST),
+    (0, 1, STRAIGHT_COST),
+    (-1, -1, DIAGONAL_COST),
+    (1, -1, DIAGONAL_COST),
+    (-1, 1, DIAGONAL_COST),
+    (1, 1, DIAGONAL_COST),
+];
+
+// Cost to reach a goal tile from every other tile within some distance of it
+pub struct FlowField {
+    // Top left corner of the tiles covered
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    cost: Vec<u32>,
+}
+
+impl FlowField {
+    // Dijkstra starting at the goal. `tile_cost` returns `None` for impassable tiles or the
+    // additional cost of entering a tile.
+    pub fn new(
+        width: i32,
+        height: i32,
+        goal: (i32, i32),
+        tile_cost: impl Fn(i32, i32) -> Option<u32>,
+    ) -> Self {
+        Self::around(width, height, goal, width.max(height), tile_cost)
+    }
+
+    // Same as `new`, but only covers tiles at most `radius` tiles away from the goal
+    pub fn around(
+        width: i32,
+        height: i32,
+        goal: (i32, i32),
+        radius: i32,
+        tile_cost: impl Fn(i32, i32) -> Option<u32>,
+    ) -> Self {
+        let left = (goal.0 - radius).clamp(0, width);
+        let top = (goal.1 - radius).clamp(0, height);
+        let right = (goal.0 + radius + 1).clamp(left, width);
+        let bottom = (goal.1 + radius + 1).clamp(top, height);
+        let mut field = Self {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+            cost: vec![UNREACHABLE; ((right - left) * (bottom - top)) as usize],
+        };
+        let Some(goal_index) = field.index(goal.0, goal.1) else {
+            return field;
+        };
+        let mut queue = BinaryHeap::new();
+        field.cost[goal_index] = 0;
+        queue.push(Reverse((0, goal.0, goal.1)));
+        while let Some(Reverse((cost, x, y))) = queue.pop() {
+            if cost > field.cost[field.index(x, y).unwrap()] {
+                continue;
+            }
+            // Stepping from a neighbor onto this tile costs the step plus this tile's extra cost
+            let Some(extra) = tile_cost(x, y) else {
+                continue;
+            };
+            for (dx, dy, step) in NEIGHBORS {
+                let (nx, ny) = (x + dx, y + dy);
+                let Some(i) = field.index(nx, ny) else {
+                    continue;
+                };
+                // Don't cut corners
+                if dx != 0
+                    && dy != 0
+                    && (tile_cost(x + dx, y).is_none() || tile_cost(x, y + dy).is_none())
+                {
+                    continue;
+                }
+                if tile_cost(nx, ny).is_none() {
+                    continue;
+                }
+                let next_cost = cost + step + extra;
+                if next_cost < field.cost[i] {
+                    field.cost[i] = next_cost;
+                    queue.push(Reverse((next_cost, nx, ny)));
+                }
+            }
+        }
+        field
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let (x, y) = (x - self.left, y - self.top);
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        } else {
+            Some((y * self.width + x) as usize)
+        }
+    }
+
+    pub fn cost_at(&self, x: i32, y: i32) -> Option<u32> {
+        self.index(x, y)
+            .map(|i| self.cost[i])
+            .filter(|&c| c != UNREACHABLE)
+    }
+
+    // Next tile towards the goal, `None` if already there or the goal is not reachable
+    pub fn next(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let current = self.cost_at(x, y)?;
+        NEIGHBORS
+            .iter()
+            .filter(|(dx, dy, _)| {
+                *dx == 0
+                    || *dy == 0
+                    || self.cost_at(x + dx, y).is_some() && self.cost_at(x, y + dy).is_some()
+            })
+            .filter_map(|(dx, dy, _)| Some((x + dx, y + dy, self.cost_at(x + dx, y + dy)?)))
+            .filter(|(_, _, c)| *c < current)
+            .min_by_key(|(_, _, c)| *c)
+            .map(|(x, y, _)| (x, y))
+    }
+
+    pub fn path(&self, x: i32, y: i32) -> Vec<(i32, i32)> {
+        std::iter::successors(Some((x, y)), |&(x, y)| self.next(x, y)).collect()
+    }
+}
+
+#[derive(Default)]
+pub struct Pathing {
+    last_refresh: i32,
+    // By goal, flying and radius
+    flow_fields: RefCell<AHashMap<(TilePosition, bool, i32), Rc<FlowField>>>,
+    // Cost of entering each tile, for ground and air units. Shared by all flow fields until
+    // the next refresh.
+    tile_costs: RefCell<AHashMap<bool, Rc<Vec<Option<u32>>>>>,
+}
+
+impl Pathing {
+    pub fn update(&mut self, frame: i32) {
+        if frame - self.last_refresh >= FLOW_FIELD_TTL {
+            self.last_refresh = frame;
+            self.flow_fields.borrow_mut().clear();
+            self.tile_costs.borrow_mut().clear();
+        }
+    }
+}
+
+impl MyModule {
+    fn tile_costs(&self, flying: bool) -> Rc<Vec<Option<u32>>> {
+        self.pathing
+            .tile_costs
+            .borrow_mut()
+            .entry(flying)
+            .or_insert_with(|| {
+                let (width, height) = (self.game.map_width(), self.game.map_height());
+                Rc::new(
+                    (0..height)
+                        .flat_map(|y| (0..width).map(move |x| TilePosition::new(x, y)))
+                        .map(|tile| {
+                            if flying {
+                                Some((THREAT_PENALTY * self.grids.air_threat_at(tile)) as u32)
+                            } else if self.game.is_walkable(tile.center().to_walk_position()) {
+                                Some((THREAT_PENALTY * self.grids.ground_threat_at(tile)) as u32)
+                            } else {
+                                None
+                            }
+                        })
+                        .collect(),
+                )
+            })
+            .clone()
+    }
+
+    fn flow_field(&self, goal: TilePosition, flying: bool, radius: i32) -> Rc<FlowField> {
+        let key = (goal, flying, radius);
+        if let Some(field) = self.pathing.flow_fields.borrow().get(&key) {
+            return field.clone();
+        }
+        let costs = self.tile_costs(flying);
+        let width = self.game.map_width();
+        let field = Rc::new(FlowField::around(
+            width,
+            self.game.map_height(),
+            (goal.x, goal.y),
+            radius,
+            |x, y| costs[(y * width + x) as usize],
+        ));
+        self.pathing
+            .flow_fields
+            .borrow_mut()
+            .insert(key, field.clone());
+        field
+    }
+
+    // Goal to use for the flow field towards `to`: The next choke on the way, or the cell
+    // containing `to` once we're in the same area. Snapped to a passable tile, a field grown
+    // from an impassable goal reaches nothing.
+    fn waypoint_goal(&self, unit: &SUnit, to: Position) -> TilePosition {
+        let tile = to.to_tile_position();
+        let goal = if unit.flying() {
+            None
+        } else {
+            self.map
+                .get_path(unit.position(), to)
+                .0
+                .first()
+                .map(|cp| cp.top.to_tile_position())
+        }
+        .unwrap_or_else(|| {
+            TilePosition::new(
+                tile.x / GOAL_CELL_SIZE * GOAL_CELL_SIZE + GOAL_CELL_SIZE / 2,
+                tile.y / GOAL_CELL_SIZE * GOAL_CELL_SIZE + GOAL_CELL_SIZE / 2,
+            )
+        });
+        let costs = self.tile_costs(unit.flying());
+        let width = self.game.map_width();
+        let passable = |t: &TilePosition| {
+            t.is_valid(&&self.game) && costs[(t.y * width + t.x) as usize].is_some()
+        };
+        Spiral { center: goal }
+            .into_iter()
+            .take(((2 * GOAL_SNAP_RADIUS + 1) * (2 * GOAL_SNAP_RADIUS + 1)) as usize)
+            .find(passable)
+            .unwrap_or(tile)
+    }
+
+    // Path avoiding known enemy defenses and armies, as tile centers
+    pub fn safe_path(&self, from: Position, to: Position, flying: bool) -> Vec<Position> {
+        let from = from.to_tile_position();
+        self.flow_field(
+            to.to_tile_position(),
+            flying,
+            self.game.map_width().max(self.game.map_height()),
+        )
+        .path(from.x, from.y)
+        .into_iter()
+        .map(|(x, y)| TilePosition::new(x, y).center())
+        .collect()
+    }
+
+    // A position a few tiles ahead on the safe path to `to`
+    pub fn safe_waypoint(&self, unit: &SUnit, to: Position) -> Option<Position> {
+        if unit.position().distance(to) < (WAYPOINT_LOOKAHEAD * 32) as f64 {
+            return Some(to);
+        }
+        let from = unit.tile_position();
+        let field = self.flow_field(
+            self.waypoint_goal(unit, to),
+            unit.flying(),
+            WAYPOINT_FIELD_RADIUS,
+        );
+        let mut next = field.next(from.x, from.y)?;
+        for _ in 1..WAYPOINT_LOOKAHEAD {
+            let Some(n) = field.next(next.0, next.1) else {
+                break;
+            };
+            next = n;
+        }
+        Some(TilePosition::new(next.0, next.1).center())
+    }
+
+    pub fn safe_move_to(&self, unit: &SUnit, to: Position) {
+        let waypoint = self.safe_waypoint(unit, to).unwrap_or(to);
+        unit.move_to(self.hazard_free_waypoint(unit, waypoint)).ok();
+    }
+
+    // Last position on the safe path to `target`, before entering enemy weapon ranges
+    pub fn safe_approach(&self, unit: &SUnit, target: Position) -> Position {
+        self.safe_path(unit.position(), target, unit.flying())
+            .into_iter()
+            .take_while(|p| self.grids.threat_at(*p, unit.flying()) <= 0.0)
+            .last()
+            .unwrap_or_else(|| unit.position())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_straight_path() {
+        let field = FlowField::new(10, 10, (9, 0), |_, _| Some(0));
+
+        assert_eq!(field.cost_at(0, 0), Some(9 * STRAIGHT_COST));
+        assert_eq!(field.path(0, 0).len(), 10);
+        assert_eq!(field.next(9, 0), None);
+    }
+
+    #[test]
+    pub fn test_walks_around_threat() {
+        // Column 5 is dangerous, except for the bottom row
+        let field = FlowField::new(10, 10, (9, 0), |x, y| {
+            Some(if x == 5 && y < 9 { 500 } else { 0 })
+        });
+
+        let path = field.path(0, 0);
+        assert!(path.iter().all(|&(x, y)| x != 5 || y == 9));
+        assert_eq!(path.last(), Some(&(9, 0)));
+    }
+
+    #[test]
+    pub fn test_unreachable_goal() {
+        // Wall at column 5
+        let field = FlowField::new(10, 10, (9, 0), |x, _| if x == 5 { None } else { Some(0) });
+
+        assert_eq!(field.cost_at(0, 0), None);
+        assert_eq!(field.next(0, 0), None);
+        assert_eq!(field.path(0, 0), vec![(0, 0)]);
+    }
+
+    #[test]
+    pub fn test_bounded_field() {
+        let field = FlowField::around(20, 20, (10, 10), 3, |_, _| Some(0));
+
+        assert_eq!(field.cost_at(13, 10), Some(3 * STRAIGHT_COST));
+        assert_eq!(field.cost_at(14, 10), None);
+        assert_eq!(field.cost_at(0, 0), None);
+    }
+
+    #[test]
+    pub fn test_no_corner_cutting() {
+        // Two blocked tiles touching diagonally
+        let field = FlowField::new(3, 3, (2, 0), |x, y| {
+            if (x, y) == (1, 0) || (x, y) == (2, 1) {
+                None
+            } else {
+                Some(0)
+            }
+        });
+
+        assert_eq!(field.cost_at(1, 1), None);
+    }
+}
@@ -0,0 +1,317 @@
+use crate::build::At;
+use crate::*;
+use ahash::AHashSet;
+
+// Tiles around the depot considered for slots
+const LAYOUT_RADIUS: i32 = 14;
+// Chokes this close to the depot get a lane to walk through
+const RAMP_RADIUS: f64 = 16.0 * 32.0;
+// Half width of lanes kept free for workers and units
+const LANE_HALF_WIDTH: i32 = 1;
+const RAMP_CLEARANCE: i32 = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlotRole {
+    Tech,
+    Defense,
+    MacroHatch,
+}
+
+impl SlotRole {
+    pub fn of(unit_type: UnitType, at: &At) -> Self {
+        if matches!(at, At::DefenseChoke) {
+            SlotRole::Defense
+        } else if unit_type.is_resource_depot() {
+            SlotRole::MacroHatch
+        } else {
+            SlotRole::Tech
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Slot {
+    // Top left tile
+    pub tile: TilePosition,
+    pub width: i32,
+    pub height: i32,
+    pub role: SlotRole,
+}
+
+impl Slot {
+    fn tiles(&self) -> impl Iterator<Item = TilePosition> + '_ {
+        (0..self.width).flat_map(move |dx| {
+            (0..self.height).map(move |dy| TilePosition::new(self.tile.x + dx, self.tile.y + dy))
+        })
+    }
+
+    fn center(&self) -> Position {
+        self.tile.to_position() + Position::new(self.width * 16, self.height * 16)
+    }
+}
+
+// Building slots of one base, planned once at the start of the game
+#[derive(Default, Debug)]
+pub struct Layout {
+    pub slots: Vec<Slot>,
+    // Mineral line, geyser gaps and lanes to ramps
+    pub reserved: AHashSet<TilePosition>,
+}
+
+impl Layout {
+    // Slots are ordered by preference
+    pub fn slots_for(
+        &self,
+        role: SlotRole,
+        width: i32,
+        height: i32,
+    ) -> impl Iterator<Item = &Slot> {
+        self.slots
+            .iter()
+            .filter(move |s| s.role == role && s.width == width && s.height == height)
+    }
+}
+
+fn reserve_rect(reserved: &mut AHashSet<TilePosition>, tl: TilePosition, width: i32, height: i32) {
+    for x in tl.x..tl.x + width {
+        for y in tl.y..tl.y + height {
+            reserved.insert(TilePosition::new(x, y));
+        }
+    }
+}
+
+fn reserve_lane(
+    reserved: &mut AHashSet<TilePosition>,
+    from: Position,
+    to: Position,
+    half_width: i32,
+) {
+    let steps = (from.distance(to) / 16.0).ceil() as i32;
+    for i in 0..=steps {
+        let p = from + (to - from) * i / steps.max(1);
+        let t = p.to_tile_position();
+        reserve_rect(
+            reserved,
+            TilePosition::new(t.x - half_width, t.y - half_width),
+            2 * half_width + 1,
+            2 * half_width + 1,
+        );
+    }
+}
+
+// Plans building slots around `depot` (top left tile of the hatchery). Resources are mineral and
+// geyser centers belonging to this base, chokes are choke centers and `other_depots` are the
+// locations of all other bases - which we might want to take later.
+pub fn plan_layout(
+    depot: TilePosition,
+    resources: &[Position],
+    chokes: &[Position],
+    other_depots: &[TilePosition],
+    buildable: impl Fn(TilePosition) -> bool,
+) -> Layout {
+    let depot_center = depot.to_position() + Position::new(64, 48);
+    let mut reserved = AHashSet::new();
+    reserve_rect(
+        &mut reserved,
+        TilePosition::new(depot.x - 1, depot.y - 1),
+        6,
+        5,
+    );
+    for od in other_depots {
+        reserve_rect(&mut reserved, TilePosition::new(od.x - 1, od.y - 1), 6, 5);
+    }
+    // Workers need to get from each patch and geyser to the depot
+    for r in resources {
+        reserve_lane(&mut reserved, *r, depot_center, LANE_HALF_WIDTH);
+    }
+    let ramps: Vec<_> = chokes
+        .iter()
+        .filter(|c| c.distance(depot_center) < RAMP_RADIUS)
+        .copied()
+        .collect();
+    for ramp in ramps.iter() {
+        reserve_lane(&mut reserved, *ramp, depot_center, LANE_HALF_WIDTH);
+        let t = ramp.to_tile_position();
+        reserve_rect(
+            &mut reserved,
+            TilePosition::new(t.x - RAMP_CLEARANCE, t.y - RAMP_CLEARANCE),
+            2 * RAMP_CLEARANCE + 1,
+            2 * RAMP_CLEARANCE + 1,
+        );
+    }
+    let mineral_line = if resources.is_empty() {
+        depot_center
+    } else {
+        resources
+            .iter()
+            .fold(Position::new(0, 0), |acc, &r| acc + r)
+            / resources.len() as i32
+    };
+    // Defense goes between the depot and the ramp, or covers the mineral line
+    let defense_anchor = ramps
+        .iter()
+        .min_by_key(|r| r.distance(depot_center) as i32)
+        .map(|&r| depot_center + (r - depot_center) / 3)
+        .unwrap_or((depot_center + mineral_line) / 2);
+    // Tech is safest behind the mineral line - or at least close to the depot
+    let tech_anchor = depot_center;
+
+    let mut occupied = AHashSet::new();
+    let mut slots = vec![];
+    let mut plan = |role: SlotRole, width: i32, height: i32, count: usize, anchor: Position| {
+        let mut candidates: Vec<_> = (-LAYOUT_RADIUS..=LAYOUT_RADIUS)
+            .flat_map(|dx| {
+                (-LAYOUT_RADIUS..=LAYOUT_RADIUS)
+                    .map(move |dy| TilePosition::new(depot.x + dx, depot.y + dy))
+            })
+            .map(|tile| Slot {
+                tile,
+                width,
+                height,
+                role,
+            })
+            .collect();
+        candidates.sort_by_key(|s| s.center().distance(anchor) as i32);
+        let mut planned = 0;
+        for slot in candidates {
+            if planned >= count {
+                break;
+            }
+            let fits = slot.tiles().all(|t| buildable(t) && !reserved.contains(&t));
+            // Keep a gap to other slots, so we don't wall in our own units
+            let padded = Slot {
+                tile: TilePosition::new(slot.tile.x - 1, slot.tile.y - 1),
+                width: width + 2,
+                height: height + 2,
+                role,
+            };
+            if fits && padded.tiles().all(|t| !occupied.contains(&t)) {
+                occupied.extend(slot.tiles());
+                slots.push(slot);
+                planned += 1;
+            }
+        }
+    };
+    plan(SlotRole::Defense, 2, 2, 4, defense_anchor);
+    plan(SlotRole::Tech, 3, 2, 6, tech_anchor);
+    plan(SlotRole::Tech, 2, 2, 2, tech_anchor);
+    plan(SlotRole::Tech, 4, 2, 1, tech_anchor);
+    plan(SlotRole::MacroHatch, 4, 3, 2, tech_anchor);
+
+    Layout { slots, reserved }
+}
+
+impl Layout {
+    pub fn new(game: &Game, map: &Map, depot: TilePosition, other_depots: &[TilePosition]) -> Self {
+        let resources: Vec<_> = game
+            .get_static_minerals()
+            .iter()
+            .chain(game.get_static_geysers().iter())
+            .map(|r| r.get_initial_position())
+            .filter(|p| p.distance(depot.center()) < 12.0 * 32.0)
+            .collect();
+        let chokes: Vec<_> = map.choke_points.iter().map(|cp| cp.top.center()).collect();
+        plan_layout(depot, &resources, &chokes, other_depots, |t| {
+            t.is_valid(&game) && game.is_buildable(t)
+        })
+    }
+}
+
+impl MyModule {
+    // Free slot at our base closest to `near`
+    pub fn planned_slot(
+        &self,
+        unit_type: UnitType,
+        at: &At,
+        near: TilePosition,
+        builder: &SUnit,
+    ) -> Option<TilePosition> {
+        if matches!(at, At::TilePosition(_) | At::Exactly(_)) {
+            return None;
+        }
+        let role = SlotRole::of(unit_type, at);
+        let size = unit_type.tile_size();
+        let base = self
+            .bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_me()).unwrap_or(false))
+            .min_by_key(|b| b.position.distance_squared(near))?;
+        // Another queued building will be placed there
+        let claimed = |slot: &Slot| slot.tiles().any(|t| self.is_tile_queued(t));
+        base.layout
+            .slots_for(role, size.x, size.y)
+            .filter(|slot| {
+                !unit_type.requires_creep() || slot.tiles().all(|t| self.game.has_creep(t))
+            })
+            .filter(|slot| !claimed(slot))
+            .find(|slot| {
+                self.game
+                    .can_build_here(&builder.unit, slot.tile, unit_type, false)
+                    .unwrap_or(false)
+            })
+            .map(|slot| slot.tile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_field(t: TilePosition) -> bool {
+        t.x >= 0 && t.y >= 0 && t.x < 64 && t.y < 64
+    }
+
+    #[test]
+    fn should_keep_mineral_line_and_ramp_free() {
+        let depot = TilePosition::new(30, 30);
+        let minerals: Vec<_> = (0..8)
+            .map(|i| TilePosition::new(23, 26 + i).center())
+            .collect();
+        let ramp = TilePosition::new(42, 31).center();
+        let natural = TilePosition::new(30, 50);
+
+        let layout = plan_layout(depot, &minerals, &[ramp], &[natural], open_field);
+
+        let lanes: Vec<_> = minerals
+            .iter()
+            .map(|m| ((*m + depot.center()) / 2).to_tile_position())
+            .collect();
+        for slot in layout.slots.iter() {
+            assert!(slot.tiles().all(|t| !layout.reserved.contains(&t)));
+            // Workers can get to the minerals
+            assert!(slot.tiles().all(|t| !lanes.contains(&t)));
+            // Not blocking the natural
+            assert!(slot
+                .tiles()
+                .all(|t| !(t.x >= 30 && t.x < 34 && t.y >= 50 && t.y < 53)));
+        }
+    }
+
+    #[test]
+    fn should_not_overlap_slots() {
+        let layout = plan_layout(TilePosition::new(30, 30), &[], &[], &[], open_field);
+
+        let mut seen = AHashSet::new();
+        for slot in layout.slots.iter() {
+            for t in slot.tiles() {
+                assert!(seen.insert(t));
+            }
+        }
+        assert_eq!(layout.slots_for(SlotRole::Tech, 3, 2).count(), 6);
+        assert_eq!(layout.slots_for(SlotRole::MacroHatch, 4, 3).count(), 2);
+    }
+
+    #[test]
+    fn defense_should_face_the_ramp() {
+        let depot = TilePosition::new(30, 30);
+        let ramp = TilePosition::new(44, 31).center();
+
+        let layout = plan_layout(depot, &[], &[ramp], &[], open_field);
+
+        let depot_distance = depot.center().distance(ramp);
+        assert_eq!(layout.slots_for(SlotRole::Defense, 2, 2).count(), 4);
+        for slot in layout.slots_for(SlotRole::Defense, 2, 2) {
+            assert!(slot.center().distance(ramp) < depot_distance);
+        }
+    }
+}
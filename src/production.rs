@@ -0,0 +1,340 @@
+use crate::*;
+use std::cmp::Reverse;
+
+pub const DEFAULT_PRIORITY: i32 = 0;
+// Running out of supply blocks everything else
+pub const SUPPLY_PRIORITY: i32 = 100;
+pub const DEFENSE_PRIORITY: i32 = 50;
+pub const TECH_PRIORITY: i32 = 20;
+// Units pumped with whatever is left
+pub const ARMY_PRIORITY: i32 = -10;
+// Frames to look ahead when estimating income for items we can't afford yet
+const INCOME_FRAMES: i32 = 24 * 60;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProductionItem {
+    Unit(UnitType),
+    Upgrade(UpgradeType),
+    Tech(TechType),
+}
+
+#[derive(Debug)]
+pub struct QueuedItem {
+    pub item: ProductionItem,
+    pub price: Gms,
+    pub priority: i32,
+    // Frame this should be started at, items with earlier deadlines get money first
+    pub deadline: i32,
+    pub builder: Option<SUnit>,
+    // Where a building will be placed, once we picked a spot
+    pub location: Option<TilePosition>,
+    pub reserved: Gms,
+    // Frames until we can afford this, given our income and everything queued before it
+    pub eta: Option<i32>,
+    requested: bool,
+}
+
+// Requested items, kept across frames as long as the strategy keeps requesting them. Money is
+// reserved by priority instead of the order of `ensure_*` calls.
+pub struct ProductionQueue {
+    pub items: Vec<QueuedItem>,
+    priority: i32,
+    deadline: Option<i32>,
+    // Item claimed by the last `start_*` call
+    claimed: Option<usize>,
+}
+
+impl Default for ProductionQueue {
+    fn default() -> Self {
+        Self {
+            items: vec![],
+            priority: DEFAULT_PRIORITY,
+            deadline: None,
+            claimed: None,
+        }
+    }
+}
+
+// Reserves `bank` for the items in order of priority and deadline. Missing money is taken from
+// future income, in the same order. Returns the total reserved.
+pub fn reserve_for(items: &mut [QueuedItem], bank: Gms, income_per_frame: (f64, f64)) -> Gms {
+    items.sort_by_key(|it| (Reverse(it.priority), it.deadline));
+    let mut bank = bank;
+    let mut total = Gms::default();
+    let mut missing = Gms::default();
+    for it in items.iter_mut() {
+        it.reserved = Gms {
+            minerals: it.price.minerals.min(bank.minerals).max(0),
+            gas: it.price.gas.min(bank.gas).max(0),
+            supply: it.price.supply.min(bank.supply).max(0),
+        };
+        bank -= it.reserved;
+        total += it.reserved;
+        missing += Gms {
+            minerals: it.price.minerals - it.reserved.minerals,
+            gas: it.price.gas - it.reserved.gas,
+            supply: 0,
+        };
+        let frames = |missing: i32, rate: f64| {
+            if missing <= 0 {
+                Some(0)
+            } else if rate <= 0.0 {
+                None
+            } else {
+                Some((missing as f64 / rate).ceil() as i32)
+            }
+        };
+        it.eta = frames(missing.minerals, income_per_frame.0)
+            .zip(frames(missing.gas, income_per_frame.1))
+            .map(|(m, g)| m.max(g));
+    }
+    total
+}
+
+// Index to insert a new item at, behind items of higher priority or earlier deadline
+pub fn queue_position(items: &[QueuedItem], priority: i32, deadline: i32) -> usize {
+    items
+        .iter()
+        .position(|it| (Reverse(it.priority), it.deadline) > (Reverse(priority), deadline))
+        .unwrap_or(items.len())
+}
+
+impl ProductionItem {
+    fn price(&self, game: &Game) -> Gms {
+        match self {
+            ProductionItem::Unit(ut) => ut.price(),
+            ProductionItem::Upgrade(upgrade) => upgrade.price(
+                game.self_()
+                    .map(|s| s.get_upgrade_level(*upgrade))
+                    .unwrap_or(0),
+            ),
+            ProductionItem::Tech(tech) => tech.price(),
+        }
+    }
+}
+
+impl MyModule {
+    // Items requested within `f` get money before items of lower priority
+    pub fn with_priority<R>(&mut self, priority: i32, f: impl FnOnce(&mut Self) -> R) -> R {
+        let old = std::mem::replace(&mut self.queue.priority, priority);
+        let result = f(self);
+        self.queue.priority = old;
+        result
+    }
+
+    // Items requested within `f` should be started at `frame`
+    pub fn with_deadline<R>(&mut self, frame: i32, f: impl FnOnce(&mut Self) -> R) -> R {
+        let old = self.queue.deadline.replace(frame);
+        let result = f(self);
+        self.queue.deadline = old;
+        result
+    }
+
+    // Drops items not requested last frame and reserves money for the others
+    pub fn update_production_queue(&mut self) {
+        self.queue.items.retain(|it| it.requested);
+        for it in self.queue.items.iter_mut() {
+            it.requested = false;
+            if it.builder.as_ref().map(|b| !b.exists()).unwrap_or(false) {
+                it.builder = None;
+            }
+        }
+        self.queue.claimed = None;
+        let incomes = self.resource_incomes();
+        let rate = |kind| {
+            incomes
+                .iter()
+                .filter(|i| i.kind == kind)
+                .map(|i| i.income(INCOME_FRAMES) as f64 / INCOME_FRAMES as f64)
+                .sum::<f64>()
+        };
+        let income = (rate(ResourceKind::Minerals), rate(ResourceKind::Gas));
+        let reserved = reserve_for(&mut self.queue.items, self.tracker.available_gms, income);
+        self.tracker.available_gms -= reserved;
+    }
+
+    // Called by `start_*` before spending: Takes the next matching queued item (or queues a new
+    // one) and makes its reserved money available again. New items are placed by priority and
+    // deadline, not behind everything requested so far.
+    pub fn claim_production(&mut self, item: ProductionItem) {
+        let frame = self.game.get_frame_count();
+        let index = match self
+            .queue
+            .items
+            .iter()
+            .position(|it| it.item == item && !it.requested)
+        {
+            Some(index) => index,
+            None => {
+                let priority = self.queue.priority;
+                let deadline = self.queue.deadline.unwrap_or(frame);
+                let index = queue_position(&self.queue.items, priority, deadline);
+                self.queue.items.insert(
+                    index,
+                    QueuedItem {
+                        item,
+                        price: item.price(&self.game),
+                        priority,
+                        deadline,
+                        builder: None,
+                        location: None,
+                        reserved: Gms::default(),
+                        eta: None,
+                        requested: false,
+                    },
+                );
+                index
+            }
+        };
+        let it = &mut self.queue.items[index];
+        it.requested = true;
+        it.priority = self.queue.priority;
+        if let Some(deadline) = self.queue.deadline {
+            it.deadline = deadline;
+        }
+        self.tracker.available_gms += std::mem::take(&mut it.reserved);
+        self.queue.claimed = Some(index);
+    }
+
+    // The unit building the item claimed last
+    pub fn assign_production_builder(&mut self, builder: &SUnit) {
+        if let Some(it) = self
+            .queue
+            .claimed
+            .and_then(|index| self.queue.items.get_mut(index))
+        {
+            it.builder = Some(builder.clone());
+        }
+    }
+
+    // The spot the building claimed last will be placed at
+    pub fn assign_production_location(&mut self, location: TilePosition) {
+        if let Some(it) = self
+            .queue
+            .claimed
+            .and_then(|index| self.queue.items.get_mut(index))
+        {
+            it.location = Some(location);
+        }
+    }
+
+    // Whether a queued building other than the one claimed last will be placed on `tile`
+    pub fn is_tile_queued(&self, tile: TilePosition) -> bool {
+        self.queue.items.iter().enumerate().any(|(i, it)| {
+            let (ProductionItem::Unit(ut), Some(location)) = (it.item, it.location) else {
+                return false;
+            };
+            let size = ut.tile_size();
+            Some(i) != self.queue.claimed
+                && (location.x..location.x + size.x).contains(&tile.x)
+                && (location.y..location.y + size.y).contains(&tile.y)
+        })
+    }
+
+    // Income items queued before the one claimed last will need, before it gets anything. Items
+    // claimed this frame already got their reservation back, or spent it.
+    pub fn promised_income(&self) -> Gms {
+        let Some(claimed) = self.queue.claimed else {
+            return Gms::default();
+        };
+        self.queue.items[..claimed]
+            .iter()
+            .filter(|it| !it.requested)
+            .map(|it| Gms {
+                minerals: it.price.minerals - it.reserved.minerals,
+                gas: it.price.gas - it.reserved.gas,
+                supply: 0,
+            })
+            .sum()
+    }
+
+    pub fn queued_gas(&self) -> i32 {
+        self.queue
+            .items
+            .iter()
+            .filter(|it| it.requested)
+            .map(|it| it.price.gas)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(unit_type: UnitType, priority: i32, deadline: i32) -> QueuedItem {
+        QueuedItem {
+            item: ProductionItem::Unit(unit_type),
+            price: unit_type.price(),
+            priority,
+            deadline,
+            builder: None,
+            location: None,
+            reserved: Gms::default(),
+            eta: None,
+            requested: true,
+        }
+    }
+
+    #[test]
+    fn should_reserve_by_priority() {
+        let mut items = vec![
+            item(UnitType::Zerg_Drone, DEFAULT_PRIORITY, 0),
+            item(UnitType::Zerg_Overlord, SUPPLY_PRIORITY, 10),
+        ];
+        let bank = Gms {
+            minerals: 100,
+            gas: 0,
+            supply: 10,
+        };
+
+        let total = reserve_for(&mut items, bank, (1.0, 0.0));
+
+        assert_eq!(items[0].item, ProductionItem::Unit(UnitType::Zerg_Overlord));
+        assert_eq!(items[0].reserved.minerals, 100);
+        assert_eq!(items[0].eta, Some(0));
+        assert_eq!(items[1].reserved.minerals, 0);
+        assert_eq!(total.minerals, 100);
+    }
+
+    #[test]
+    fn should_reserve_future_income_in_order() {
+        let mut items = vec![
+            item(UnitType::Zerg_Spawning_Pool, DEFAULT_PRIORITY, 0),
+            item(UnitType::Zerg_Drone, DEFAULT_PRIORITY, 5),
+        ];
+        let bank = Gms {
+            minerals: 150,
+            gas: 0,
+            supply: 10,
+        };
+
+        reserve_for(&mut items, bank, (1.0, 0.0));
+
+        // The pool misses 50 minerals, the drone has to wait for those as well
+        assert_eq!(items[0].eta, Some(50));
+        assert_eq!(items[1].eta, Some(100));
+    }
+
+    #[test]
+    fn should_queue_new_items_by_priority() {
+        let items = vec![
+            item(UnitType::Zerg_Overlord, SUPPLY_PRIORITY, 0),
+            item(UnitType::Zerg_Drone, DEFAULT_PRIORITY, 0),
+            item(UnitType::Zerg_Zergling, ARMY_PRIORITY, 0),
+        ];
+
+        assert_eq!(queue_position(&items, DEFENSE_PRIORITY, 0), 1);
+        assert_eq!(queue_position(&items, DEFAULT_PRIORITY, 10), 2);
+        assert_eq!(queue_position(&items, ARMY_PRIORITY, 10), 3);
+    }
+
+    #[test]
+    fn should_not_promise_gas_without_income() {
+        let mut items = vec![item(UnitType::Zerg_Lair, DEFAULT_PRIORITY, 0)];
+
+        reserve_for(&mut items, Gms::default(), (1.0, 0.0));
+
+        assert_eq!(items[0].eta, None);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::*;
+
+const QUEEN_DISTANCE_TO_VANGUARD: i32 = 5 * 32;
+const CAST_RANGE: i32 = 9 * 32;
+const ENSNARE_RADIUS: i32 = 2 * 32;
+// Minimum number of units to be hit by Ensnare
+const MIN_ENSNARED: i32 = 4;
+// Enemies further away from our army are no immediate concern
+const ENGAGE_DISTANCE: f64 = 10.0 * 32.0;
+// No need to keep track of more units than that
+const MAX_PARASITED: usize = 3;
+
+fn is_ensnare_target(unit: &SUnit) -> bool {
+    matches!(
+        unit.get_type(),
+        UnitType::Terran_Marine
+            | UnitType::Terran_Firebat
+            | UnitType::Terran_Medic
+            | UnitType::Terran_Ghost
+            | UnitType::Protoss_Corsair
+            | UnitType::Zerg_Mutalisk
+            | UnitType::Zerg_Scourge
+    )
+}
+
+fn is_broodling_target(unit: &SUnit) -> bool {
+    matches!(
+        unit.get_type(),
+        UnitType::Terran_Siege_Tank_Tank_Mode
+            | UnitType::Terran_Siege_Tank_Siege_Mode
+            | UnitType::Protoss_High_Templar
+    )
+}
+
+// Units moving all over the map, which will show us a lot
+fn is_parasite_target(unit: &SUnit) -> bool {
+    unit.get_type().space_provided() > 0 && unit.get_type().is_flyer()
+        || matches!(
+            unit.get_type(),
+            UnitType::Zerg_Overlord | UnitType::Protoss_Observer
+        )
+}
+
+impl MyModule {
+    fn ensnare_target(&self, queen: &SUnit) -> Option<Position> {
+        let army = self.vanguard.as_ref()?.position();
+        let enemies: Vec<_> = self
+            .units
+            .enemy
+            .iter()
+            .filter(|e| {
+                e.exists()
+                    && e.ensnare_timer() == 0
+                    && is_ensnare_target(e)
+                    && e.position().distance(army) < ENGAGE_DISTANCE
+                    && e.position().distance(queen.position()) < 2.0 * CAST_RANGE as f64
+            })
+            .collect();
+        let (position, hit) = best_spell_position(&enemies, ENSNARE_RADIUS, |_| 1)?;
+        (hit >= MIN_ENSNARED).then_some(position)
+    }
+
+    fn broodling_target(&self, queen: &SUnit) -> Option<SUnit> {
+        self.units
+            .enemy
+            .iter()
+            .filter(|e| {
+                e.targetable()
+                    && is_broodling_target(e)
+                    && e.position().distance(queen.position()) < 2.0 * CAST_RANGE as f64
+            })
+            .min_by_key(|e| e.position().distance(queen.position()) as i32)
+            .cloned()
+    }
+
+    fn parasite_target(&self, queen: &SUnit) -> Option<SUnit> {
+        if self.units.parasited.len() >= MAX_PARASITED {
+            return None;
+        }
+        self.units
+            .enemy
+            .iter()
+            .filter(|e| {
+                e.targetable()
+                    && !e.parasited()
+                    && is_parasite_target(e)
+                    && e.position().distance(queen.position()) < 2.0 * CAST_RANGE as f64
+            })
+            .min_by_key(|e| e.position().distance(queen.position()) as i32)
+            .cloned()
+    }
+
+    fn cast_on_unit(&self, queen: &SUnit, tech: TechType, target: &SUnit) {
+        if queen.position().distance(target.position()) as i32 <= CAST_RANGE {
+            cvis().log_unit_frame(queen, || format!("Casting {:?} on {}", tech, target.id()));
+            queen.use_tech_unit(tech, target).ok();
+        } else {
+            self.safe_move_to(queen, target.position());
+        }
+    }
+
+    // Ensnare, Broodlings and Parasite
+    pub fn perform_queens(&mut self) {
+        let queens: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| u.get_type() == UnitType::Zerg_Queen)
+            .cloned()
+            .collect();
+        for p in self.units.parasited.iter() {
+            cvis().draw_circle(p.position().x, p.position().y, 16, Color::Yellow);
+        }
+        let me = self.game.self_().unwrap();
+        for queen in queens.iter() {
+            self.tracker.reserve_unit(queen);
+            let energy = queen.energy();
+            if me.has_researched(TechType::Ensnare) && energy >= TechType::Ensnare.energy_cost() {
+                if let Some(target) = self.ensnare_target(queen) {
+                    if queen.position().distance(target) as i32 <= CAST_RANGE {
+                        cvis().log_unit_frame(queen, || "Casting Ensnare");
+                        queen.use_tech_position(TechType::Ensnare, target).ok();
+                    } else {
+                        self.safe_move_to(queen, target);
+                    }
+                    continue;
+                }
+            }
+            if me.has_researched(TechType::Spawn_Broodlings)
+                && energy >= TechType::Spawn_Broodlings.energy_cost()
+            {
+                if let Some(target) = self.broodling_target(queen) {
+                    self.cast_on_unit(queen, TechType::Spawn_Broodlings, &target);
+                    continue;
+                }
+            }
+            if energy >= TechType::Parasite.energy_cost() {
+                if let Some(target) = self.parasite_target(queen) {
+                    self.cast_on_unit(queen, TechType::Parasite, &target);
+                    continue;
+                }
+            }
+            self.stay_behind_vanguard(queen, QUEEN_DISTANCE_TO_VANGUARD);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use crate::combat_sim::*;
+use crate::*;
+
+#[derive(Copy, Clone)]
+pub struct RunbyParams {
+    // Less lings won't do any real damage
+    pub min_units: usize,
+    // Percentage of lings we accept to lose on the way in
+    pub max_loss_percent: usize,
+}
+
+impl Default for RunbyParams {
+    fn default() -> Self {
+        Self {
+            min_units: 6,
+            max_loss_percent: 34,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RunbyPhase {
+    Idle,
+    Running,
+    Raiding,
+    Retreating,
+}
+
+impl Default for RunbyPhase {
+    fn default() -> Self {
+        Self::Idle
+    }
+}
+
+#[derive(Default)]
+pub struct Runby {
+    pub phase: RunbyPhase,
+    pub target: Option<TilePosition>,
+    pub phase_start: i32,
+    pub runners: Vec<SUnit>,
+}
+
+impl Runby {
+    fn switch_to(&mut self, phase: RunbyPhase, frame: i32) {
+        if self.phase != phase {
+            cvis().log(|| format!("Run-by: {:?} -> {:?}", self.phase, phase));
+            self.phase = phase;
+            self.phase_start = frame;
+        }
+    }
+}
+
+// Don't simulate a run-by every frame
+const RUNBY_CHECK_INTERVAL: i32 = 48;
+// Enemies this close to the path are part of the defense we need to get past
+const PATH_DEFENSE_RADIUS: f64 = 8.0 * 32.0;
+// Lings closer than this to the worker line start raiding
+const RAID_RADIUS: f64 = 6.0 * 32.0;
+const SIM_FRAMES: i32 = 12 * 24;
+
+impl MyModule {
+    // Ground units and static defense which can shoot at lings running along the path
+    fn path_defenders(&self, path: &[Position]) -> Vec<SUnit> {
+        self.units
+            .enemy
+            .iter()
+            .filter(|e| {
+                !e.missing()
+                    && e.completed()
+                    && !e.get_type().is_worker()
+                    && e.get_ground_weapon().weapon_type != WeaponType::None
+                    && path
+                        .iter()
+                        .any(|p| p.distance(e.position()) < PATH_DEFENSE_RADIUS)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Enemy base, whose main path is blocked by defense our lings could run past
+    fn runby_target(&self, lings: &[SUnit], params: &RunbyParams) -> Option<TilePosition> {
+        let home = self.forward_base()?.position();
+        let walkability = |x, y| {
+            Position::new(x, y).is_valid(&&self.game) && self.game.is_walkable((x / 8, y / 8))
+        };
+        self.bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false))
+            .map(|b| b.position)
+            .find(|&base| {
+                let worker_line = self.worker_line(base);
+                let path: Vec<_> = self
+                    .map
+                    .get_path(home, worker_line)
+                    .0
+                    .iter()
+                    .map(|cp| cp.top.center())
+                    .chain(std::iter::once(worker_line))
+                    .collect();
+                let blocked = path.iter().any(|p| self.grids.ground_threat_at(*p) > 0.0);
+                if !blocked {
+                    return false;
+                }
+                let mut sim = Simulator {
+                    player_a: Player {
+                        agents: lings.iter().map(Agent::from_unit).collect(),
+                        script: Runner {
+                            goal: (worker_line.x, worker_line.y),
+                        },
+                    },
+                    player_b: Player {
+                        agents: self
+                            .path_defenders(&path)
+                            .iter()
+                            .map(Agent::from_unit)
+                            .collect(),
+                        script: Attacker::new(),
+                    },
+                    walkability,
+                };
+                sim.simulate_for(SIM_FRAMES);
+                let lost = sim.player_a.agents.iter().filter(|a| !a.is_alive).count();
+                cvis().log(|| {
+                    format!(
+                        "Run-by to {:?} would lose {lost} of {} lings",
+                        base,
+                        lings.len()
+                    )
+                });
+                lost * 100 <= lings.len() * params.max_loss_percent
+            })
+    }
+
+    // Speedlings run past static defense or a contain to raid the worker line, and get out
+    // again once the defense responds
+    pub fn perform_runby(&mut self, params: RunbyParams) -> Result<(), FailureReason> {
+        let frame = self.game.get_frame_count();
+        if self.runby.phase == RunbyPhase::Idle {
+            if frame - self.runby.phase_start < RUNBY_CHECK_INTERVAL {
+                return Err(FailureReason::misc("Run-by not checked yet"));
+            }
+            self.runby.phase_start = frame;
+            let lings: Vec<_> = self
+                .tracker
+                .available_units
+                .iter()
+                .filter(|u| u.get_type() == UnitType::Zerg_Zergling && u.has_speed_upgrade())
+                .cloned()
+                .collect();
+            if lings.len() < params.min_units {
+                return Err(FailureReason::misc("Not enough speedlings for a run-by"));
+            }
+            let Some(target) = self.runby_target(&lings, &params) else {
+                return Err(FailureReason::misc("No run-by target"));
+            };
+            self.runby.target = Some(target);
+            self.runby.runners = lings;
+            self.runby.switch_to(RunbyPhase::Running, frame);
+        }
+        let Some(target) = self.runby.target else {
+            self.runby = Runby::default();
+            return Err(FailureReason::misc("No run-by target"));
+        };
+        let available = &self.tracker.available_units;
+        self.runby
+            .runners
+            .retain(|u| u.exists() && available.contains(u));
+        let runners = self.runby.runners.clone();
+        if runners.is_empty() {
+            self.runby = Runby::default();
+            return Err(FailureReason::misc("Run-by lings are gone"));
+        }
+        for r in runners.iter() {
+            self.tracker.reserve_unit(r);
+        }
+        let worker_line = self.worker_line(target);
+        cvis().draw_circle(worker_line.x, worker_line.y, 48, Color::Orange);
+
+        // The defense responds if their army shows up near our lings
+        let runner_value: i32 = runners
+            .iter()
+            .map(|u| self.value_of(u.get_type(), true))
+            .sum();
+        let responding_value: i32 = self
+            .units
+            .enemy
+            .iter()
+            .filter(|e| {
+                !e.missing()
+                    && is_attacker(e)
+                    && e.get_type().can_move()
+                    && runners
+                        .iter()
+                        .any(|r| r.position().distance(e.position()) < 7.0 * 32.0)
+            })
+            .map(|e| self.value_of(e.get_type(), false))
+            .sum();
+
+        match self.runby.phase {
+            RunbyPhase::Running => {
+                let arrived = runners
+                    .iter()
+                    .filter(|r| r.position().distance(worker_line) < RAID_RADIUS)
+                    .count();
+                if responding_value > runner_value {
+                    // Intercepted on the way
+                    self.runby.switch_to(RunbyPhase::Retreating, frame);
+                } else if 2 * arrived >= runners.len() {
+                    self.runby.switch_to(RunbyPhase::Raiding, frame);
+                }
+            }
+            RunbyPhase::Raiding => {
+                if responding_value > runner_value / 2 {
+                    self.runby.switch_to(RunbyPhase::Retreating, frame);
+                }
+            }
+            RunbyPhase::Retreating => {
+                if responding_value == 0 && frame - self.runby.phase_start > 48 {
+                    // Leave the lings to the squad again
+                    self.runby = Runby {
+                        phase_start: frame,
+                        ..Default::default()
+                    };
+                    return Ok(());
+                }
+            }
+            RunbyPhase::Idle => unreachable!(),
+        }
+
+        match self.runby.phase {
+            RunbyPhase::Running => {
+                for r in runners.iter() {
+                    // Threat aware, so we go around the defense if possible
+                    self.safe_move_to(r, worker_line);
+                }
+            }
+            RunbyPhase::Raiding => {
+                for r in runners.iter() {
+                    let victim = self
+                        .units
+                        .all_in_range(r, 8 * 32)
+                        .filter(|e| e.player().is_enemy() && e.targetable())
+                        .max_by_key(|e| {
+                            (
+                                e.get_type().is_worker(),
+                                self.target_priority(e),
+                                -(e.position().distance(r.position()) as i32),
+                            )
+                        })
+                        .cloned();
+                    if let Some(victim) = victim {
+                        self.engage(r, &victim);
+                    } else {
+                        self.safe_move_to(r, worker_line);
+                    }
+                }
+            }
+            RunbyPhase::Retreating => {
+                let home = self
+                    .forward_base()
+                    .map(|b| b.position())
+                    .unwrap_or(worker_line);
+                for r in runners.iter() {
+                    self.flee(r, home);
+                }
+            }
+            RunbyPhase::Idle => unreachable!(),
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{cvis, CherryVisOutput, MyModule, SPlayer, SUnit, Units};
+use crate::{cvis, CherryVisOutput, Layout, MyModule, SPlayer, SUnit, Units};
 use ahash::AHashMap;
 use rsbwapi::Game;
 use rsbwapi::TilePosition;
@@ -10,6 +10,18 @@ pub struct SBase {
     pub elevation_level: i32,
     pub player: Option<SPlayer>,
     pub resource_depot: Option<SUnit>,
+    pub layout: Layout,
+    // Mineral fields with minerals left, as far as we know
+    pub mineral_fields: usize,
+    // Our drones mining (or idling) here
+    pub workers: usize,
+}
+
+impl SBase {
+    // Two drones per mineral field, more won't add much
+    pub fn worker_capacity(&self) -> usize {
+        self.mineral_fields * 2
+    }
 }
 
 #[derive(Default)]
@@ -19,6 +31,7 @@ pub struct Bases {
 
 impl Bases {
     pub fn new(module: &MyModule) -> Self {
+        let positions: Vec<_> = module.map.bases.iter().map(|b| b.position).collect();
         let all: AHashMap<_, _> = module
             .map
             .bases
@@ -37,6 +50,18 @@ impl Bases {
                         elevation_level: module.game.get_ground_height(b.position),
                         player: None,
                         resource_depot: None,
+                        mineral_fields: 0,
+                        workers: 0,
+                        layout: Layout::new(
+                            &module.game,
+                            &module.map,
+                            b.position,
+                            &positions
+                                .iter()
+                                .copied()
+                                .filter(|&p| p != b.position)
+                                .collect::<Vec<_>>(),
+                        ),
                     },
                 )
             })
@@ -64,6 +89,23 @@ impl Bases {
                 .next()
                 .cloned();
             base.player = base.resource_depot.as_ref().map(|it| it.player());
+            base.mineral_fields = units
+                .all()
+                .filter(|it| {
+                    it.get_type().is_mineral_field()
+                        && it.resources() > 0
+                        && it.tile_position().distance_squared(base.position) < 100
+                })
+                .count();
+            base.workers = units
+                .mine_all
+                .iter()
+                .filter(|it| {
+                    it.get_type().is_worker()
+                        && (it.gathering_minerals() || it.idle())
+                        && it.tile_position().distance_squared(base.position) < 100
+                })
+                .count();
         }
     }
 }
@@ -44,7 +44,9 @@ impl Squad {
                         })
                     })
             })
-            .next();
+            .next()
+            // If we're busy hitting their buildings, we might be better off trading bases
+            .filter(|offender| !module.should_base_trade(offender));
         let base = base.position();
         if let Some(target) = offender {
             assert!(!target.get_type().is_worker());
@@ -84,6 +86,7 @@ impl Squad {
                 u.get_type().can_move()
                     && !u.get_type().is_worker()
                     && u.get_type() != UnitType::Zerg_Larva
+                    && u.get_type() != UnitType::Zerg_Overlord
                     && tracker.try_reserve_unit(*u).is_some()
             }) {
                 if is_attacker(unit) && should_attack {
@@ -102,6 +105,7 @@ impl Squad {
             .chain(fall_backers.iter())
             .filter(|u| is_attacker(u))
             .min_by_key(|u| module.map.get_path(u.position(), self.target).1);
+        module.vanguard = vanguard.map(|u| (*u).clone());
         let vanguard = match vanguard {
             Some(x) => x,
             None => {
@@ -119,18 +123,17 @@ impl Squad {
 
         for unit in fall_backers.iter() {
             let close_to_base = module.estimate_frames_to(unit, base) < 48;
-            if unit.get_type() == UnitType::Zerg_Overlord
-                || !close_to_base
-                    && enemies
-                        .iter()
-                        .any(|e| module.frames_to_engage(e, unit, 64) < 48)
+            if !close_to_base
+                && enemies
+                    .iter()
+                    .any(|e| module.frames_to_engage(e, unit, 64) < 48)
             {
                 if !close_to_base {
                     cvis().log_unit_frame(unit, || "Flee");
                     module.flee(unit, base);
                 }
             } else if unit.distance_to(*vanguard) > 256 || !unit.get_type().can_attack() {
-                unit.move_to(vanguard.position()).ok();
+                module.safe_move_to(unit, vanguard.position());
             } else {
                 let target = module
                     .units
@@ -219,7 +222,10 @@ impl Squad {
                 //     Color::Black,
                 // );
                 cvis().log_unit_frame(&u, || format!("ATK POS {} S:{}", self.target, u.sleeping()));
-                u.attack_position(self.target).ok();
+                // Attack-move along the safe path, so we don't run into static defense on the way
+                let waypoint = module.safe_waypoint(&u, self.target).unwrap_or(self.target);
+                u.attack_position(module.hazard_free_waypoint(&u, waypoint))
+                    .ok();
                 module.tracker.available_units.push(u);
             }
         }
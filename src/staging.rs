@@ -0,0 +1,153 @@
+use crate::*;
+
+// Units closer than this to the staging position are considered gathered
+const STAGING_RADIUS: f64 = 6.0 * 32.0;
+// Don't stage further than this along the way to the target
+const MAX_STAGING_PATH_FRACTION: f64 = 0.5;
+// Units closer than this to enemies are fighting, the squad takes care of them
+const COMBAT_RANGE: i32 = 10 * 32;
+// Units further ahead of the advancing group than this wait for the rest
+const MAX_LEAD: f64 = 3.0 * 32.0;
+
+#[derive(Default)]
+pub struct Staging {
+    position: Option<Position>,
+    // Frame the first unit reached the staging position
+    gathering_since: Option<i32>,
+    // Frame the army left the staging position
+    advancing_since: Option<i32>,
+}
+
+impl MyModule {
+    // Last choke on the way to the target without enemy threat, but not too far out
+    fn staging_position(&self, target: Position) -> Option<Position> {
+        let home = self.forward_base()?.position();
+        let (chokes, length) = self.map.get_path(home, target);
+        let max_distance = length as f64 * MAX_STAGING_PATH_FRACTION;
+        let staging = chokes
+            .iter()
+            .map(|cp| cp.top.center())
+            .take_while(|p| {
+                p.distance(home) <= max_distance && self.grids.ground_threat_at(*p) <= 0.0
+            })
+            .last()
+            .unwrap_or(home);
+        Some(staging)
+    }
+
+    fn in_combat(&self, unit: &SUnit) -> bool {
+        self.units
+            .all_in_range(unit, COMBAT_RANGE)
+            .any(|e| e.player().is_enemy() && !e.missing() && e.get_type().can_attack())
+            || self.grids.threat_at(unit.position(), unit.flying()) > 0.0
+    }
+
+    // Gather the army at a staging position until it is strong enough (or we waited long enough),
+    // then move out together. Units fighting are left to the squad.
+    pub fn perform_staging(&mut self, attack_params: &AttackParams, target: Position) {
+        if attack_params.min_army_value <= 0 {
+            return;
+        }
+        let frame = self.game.get_frame_count();
+        let army: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| {
+                is_attacker(u) && u.get_type().can_move() && u.get_type() != UnitType::Zerg_Overlord
+            })
+            .cloned()
+            .collect();
+        if self.staging.advancing_since.is_none() {
+            self.staging.position = self.staging_position(target);
+        }
+        let Some(staging) = self.staging.position else {
+            return;
+        };
+        cvis().draw_circle(staging.x, staging.y, STAGING_RADIUS as i32, Color::Purple);
+
+        let distance_to_target = |p: Position| self.map.get_path(p, target).1;
+        let staging_distance = distance_to_target(staging);
+        match self.staging.advancing_since {
+            None => {
+                let gathered_value: i32 = army
+                    .iter()
+                    .filter(|u| !self.in_combat(u))
+                    .filter(|u| u.position().distance(staging) <= STAGING_RADIUS)
+                    .map(|u| self.value_of(u.get_type(), true))
+                    .sum();
+                // Only wait for the army while there is something to wait with
+                let gathering_since = if gathered_value > 0 {
+                    *self.staging.gathering_since.get_or_insert(frame)
+                } else {
+                    self.staging.gathering_since = None;
+                    frame
+                };
+                let waited = frame - gathering_since;
+                cvis().log(|| format!("Staging: {gathered_value} gathered, waited {waited}"));
+                if gathered_value >= attack_params.min_army_value
+                    || waited >= attack_params.max_staging_frames
+                {
+                    self.staging.advancing_since = Some(frame);
+                    return;
+                }
+                let idle: Vec<_> = army.iter().filter(|u| !self.in_combat(u)).collect();
+                for unit in idle {
+                    self.tracker.reserve_unit(unit);
+                    if unit.position().distance(staging) > STAGING_RADIUS / 2.0 {
+                        self.safe_move_to(unit, staging);
+                    }
+                }
+            }
+            Some(advancing_since) => {
+                // Reinforcements gather at the staging position, the others move out together.
+                // Units already fighting still count for the strength of the group.
+                let (reinforcements, group): (Vec<_>, Vec<_>) = army
+                    .iter()
+                    .partition(|u| distance_to_target(u.position()) > staging_distance + 64);
+                let group_value: i32 = group
+                    .iter()
+                    .map(|u| self.value_of(u.get_type(), true))
+                    .sum();
+                if group_value < attack_params.min_army_value / 2 {
+                    cvis().log(|| format!("Staging: group too weak ({group_value}), regrouping"));
+                    self.staging = Staging::default();
+                    return;
+                }
+                let idle: Vec<_> = reinforcements
+                    .iter()
+                    .filter(|u| !self.in_combat(u))
+                    .collect();
+                for unit in idle {
+                    self.tracker.reserve_unit(unit);
+                    self.safe_move_to(unit, staging);
+                }
+                let Some(slowest) = group
+                    .iter()
+                    .map(|u| u.top_speed())
+                    .min_by(|a, b| a.total_cmp(b))
+                else {
+                    return;
+                };
+                // Where the slowest unit would be by now
+                let path = self.safe_path(staging, target, false);
+                let Some(&front) = path
+                    .get(((frame - advancing_since) as f64 * slowest / 32.0) as usize)
+                    .or(path.last())
+                else {
+                    return;
+                };
+                cvis().draw_circle(front.x, front.y, 32, Color::Purple);
+                let front_distance = distance_to_target(front) as f64;
+                let idle: Vec<_> = group.iter().filter(|u| !self.in_combat(u)).collect();
+                for unit in idle {
+                    if (distance_to_target(unit.position()) as f64) < front_distance - MAX_LEAD {
+                        self.tracker.reserve_unit(unit);
+                        cvis().log_unit_frame(unit, || "Waiting for group");
+                        self.safe_move_to(unit, front);
+                    }
+                }
+            }
+        }
+    }
+}
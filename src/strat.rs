@@ -1,5 +1,6 @@
-use crate::MyModule;
+use crate::{BuildOrder, MyModule};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fs::File;
 use std::path::Path;
 
@@ -53,8 +54,8 @@ pub fn update_strategy_records(
 }
 
 pub struct Strategy {
-    pub name: &'static str,
-    pub func: &'static dyn Fn(&mut MyModule) -> anyhow::Result<()>,
+    pub name: Cow<'static, str>,
+    pub func: Box<dyn Fn(&mut MyModule) -> anyhow::Result<()>>,
 }
 
 impl Strategy {
@@ -63,10 +64,19 @@ impl Strategy {
             name: std::any::type_name::<T>()
                 .split(':')
                 .last()
-                .expect("Strategy has no name"),
-            func: strat,
+                .expect("Strategy has no name")
+                .into(),
+            func: Box::new(strat),
         }
     }
+
+    pub fn from_build_order(build_order: BuildOrder) -> Self {
+        Self {
+            name: build_order.name.clone().into(),
+            func: Box::new(move |module: &mut MyModule| module.execute_build_order(&build_order)),
+        }
+    }
+
     pub fn tick(&self, module: &mut MyModule) -> anyhow::Result<()> {
         (self.func)(module)
     }
@@ -75,15 +85,15 @@ impl Strategy {
         let rnd = (((enemy as *const str as *const u8 as usize) / 3 + 1337) * 7 % 5) as i32;
         let Some(record) = records
             .iter()
-            .filter(|r| &r.strategy == self.name && &r.enemy == enemy && &r.map == map)
+            .filter(|r| r.strategy == self.name && &r.enemy == enemy && &r.map == map)
             .next()
             .or_else(|| {
                 records
                     .iter()
-                    .filter(|r| &r.strategy == self.name && (&r.enemy == enemy || &r.map == map))
+                    .filter(|r| r.strategy == self.name && (&r.enemy == enemy || &r.map == map))
                     .next()
             })
-            .or_else(|| records.iter().filter(|r| &r.strategy == self.name).next())
+            .or_else(|| records.iter().filter(|r| r.strategy == self.name).next())
             else { return 0.5 };
         return (record.wins + rnd) as f32 / (record.losses + record.wins + rnd) as f32;
     }
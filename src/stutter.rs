@@ -0,0 +1,139 @@
+use crate::*;
+use ahash::AHashMap;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Frames to spare for the command to arrive, on top of latency
+const COMMAND_SLACK: i32 = 2;
+
+#[derive(Default, serde::Serialize)]
+pub struct MicroMetrics {
+    // Shots fired by our units
+    attacks: AtomicU64,
+    // Frames our units could have fired at an enemy in range, but didn't
+    lost_attack_frames: AtomicU64,
+    // Attack commands not issued due to another pending command
+    swallowed_attacks: AtomicU64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StutterAction {
+    // Attack animation is playing, any command now would cancel the attack
+    Wait,
+    // Weapon is cooling down long enough to move and come back
+    Move,
+    // Issue the attack now, so it arrives in time for the weapon to be ready
+    Attack,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct MicroState {
+    last_cooldown: i32,
+    animation_end: i32,
+    // Last frame the unit was told to engage an enemy in range
+    in_range_frame: i32,
+    last_seen: i32,
+}
+
+impl MicroState {
+    fn action(&self, frame: i32, cooldown: i32, latency: i32, turn_frames: i32) -> StutterAction {
+        if frame < self.animation_end {
+            StutterAction::Wait
+        } else if cooldown > latency + turn_frames + COMMAND_SLACK {
+            StutterAction::Move
+        } else {
+            StutterAction::Attack
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Stutter {
+    states: RefCell<AHashMap<UnitId, MicroState>>,
+}
+
+impl Stutter {
+    // Detect fired shots by the cooldown going up and track the attack animation following them
+    pub fn update(&mut self, frame: i32, units: &Units) {
+        let states = self.states.get_mut();
+        for u in units
+            .my_completed
+            .iter()
+            .filter(|u| u.get_type().can_attack() && u.get_type().can_move())
+        {
+            let state = states.entry(u.id()).or_default();
+            let cooldown = u.cooldown();
+            if cooldown > state.last_cooldown {
+                state.animation_end = frame + stop_frames(u.get_type());
+                global_metric
+                    .micro_metrics
+                    .attacks
+                    .fetch_add(1, Ordering::Relaxed);
+            } else if cooldown == 0 && state.in_range_frame == frame - 1 && !u.attacking() {
+                global_metric
+                    .micro_metrics
+                    .lost_attack_frames
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            state.last_cooldown = cooldown;
+            state.last_seen = frame;
+        }
+        states.retain(|_, s| s.last_seen == frame);
+    }
+}
+
+impl MyModule {
+    pub fn stutter_action(&self, unit: &SUnit) -> StutterAction {
+        self.stutter
+            .states
+            .borrow()
+            .get(&unit.id())
+            .copied()
+            .unwrap_or_default()
+            .action(
+                self.game.get_frame_count(),
+                unit.cooldown(),
+                self.game.get_latency_frames(),
+                unit.frames_to_turn_180(),
+            )
+    }
+
+    pub fn stutter_attack(&self, unit: &SUnit, enemy: &SUnit) {
+        if unit.is_in_weapon_range(enemy) {
+            if let Some(state) = self.stutter.states.borrow_mut().get_mut(&unit.id()) {
+                state.in_range_frame = self.game.get_frame_count();
+            }
+        }
+        if let Ok(false) = unit.attack(enemy) {
+            global_metric
+                .micro_metrics
+                .swallowed_attacks
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_wait_for_attack_animation() {
+        let state = MicroState {
+            animation_end: 105,
+            ..Default::default()
+        };
+
+        assert_eq!(state.action(103, 20, 2, 2), StutterAction::Wait);
+        assert_eq!(state.action(105, 18, 2, 2), StutterAction::Move);
+    }
+
+    #[test]
+    fn should_attack_ahead_of_cooldown_by_latency() {
+        let state = MicroState::default();
+
+        assert_eq!(state.action(100, 7, 2, 2), StutterAction::Move);
+        assert_eq!(state.action(100, 6, 2, 2), StutterAction::Attack);
+        assert_eq!(state.action(100, 0, 2, 2), StutterAction::Attack);
+    }
+}
@@ -23,6 +23,8 @@ pub struct Units {
     pub my_completed: Vec<SUnit>,
     pub mine_all: Vec<SUnit>,
     pub enemy: Vec<SUnit>,
+    // Enemy units carrying our parasite, they give us vision wherever they go
+    pub parasited: Vec<SUnit>,
     pub all_rstar: RTree<SUnit>,
     pub clusters: Vec<Rc<Cluster>>,
 }
@@ -90,6 +92,9 @@ impl Units {
             {
                 inner.mining_frames = old.mining_frames + 1;
             }
+            if !inner.is_visible {
+                inner.resources = old.resources;
+            }
             if !inner.detected {
                 inner.hit_points = old.hit_points;
                 inner.shields = old.shields;
@@ -131,6 +136,12 @@ impl Units {
             .filter(|it| it.player().is_enemy())
             .cloned()
             .collect();
+        self.parasited = self
+            .enemy
+            .iter()
+            .filter(|it| it.exists() && it.parasited())
+            .cloned()
+            .collect();
         self.minerals = self
             .all
             .values()
@@ -204,6 +215,10 @@ pub enum PendingGoal {
     GatherGas(SUnit),
     GatherMinerals(SUnit),
     Upgrade(UpgradeType),
+    Research(TechType),
+    UseTech(TechType),
+    Burrow,
+    Unburrow,
     MoveTo(Position),
     AttackPosition(Position),
     Attack(SUnit),
@@ -279,6 +294,10 @@ impl SUnit {
         let inner = self.inner.borrow();
         match inner.pending_goal.goal {
             PendingGoal::Upgrade(_)
+            | PendingGoal::Research(_)
+            | PendingGoal::UseTech(_)
+            | PendingGoal::Burrow
+            | PendingGoal::Unburrow
             | PendingGoal::Build(_)
             | PendingGoal::Morph(_)
             | PendingGoal::Train(_)
@@ -298,6 +317,10 @@ impl SUnit {
         self.inner.borrow().lockdown_timer
     }
 
+    pub fn irradiate_timer(&self) -> i32 {
+        self.inner.borrow().irradiate_timer
+    }
+
     pub fn stasis_timer(&self) -> i32 {
         self.inner.borrow().stasis_timer
     }
@@ -326,6 +349,10 @@ impl SUnit {
         self.inner.borrow().is_constructing
     }
 
+    pub fn last_seen(&self) -> i32 {
+        self.inner.borrow().last_seen
+    }
+
     pub fn last_attack_frame(&self) -> i32 {
         self.inner.borrow().last_attack_frame
     }
@@ -416,6 +443,10 @@ impl SUnit {
         80_i32.saturating_sub(self.inner.borrow().mining_frames)
     }
 
+    pub fn resources(&self) -> i32 {
+        self.inner.borrow().resources
+    }
+
     pub fn future_type(&self) -> UnitType {
         let t = self.build_type();
         if t != UnitType::None {
@@ -445,6 +476,10 @@ impl SUnit {
         self.inner.borrow().target.unwrap().cloned()
     }
 
+    pub fn order_target_position(&self) -> Option<Position> {
+        self.inner.borrow().order_target_position
+    }
+
     pub fn get_order_target(&self) -> Option<SUnit> {
         self.inner.borrow().order_target()
     }
@@ -535,6 +570,59 @@ impl SUnit {
         inner.act(|_| self.unit.upgrade(ut), PendingGoal::Upgrade(ut), 3)
     }
 
+    pub fn research(&self, tech: TechType) -> BwResult<bool> {
+        let mut inner = self.inner.borrow_mut();
+        inner.act(|_| self.unit.research(tech), PendingGoal::Research(tech), 3)
+    }
+
+    pub fn use_tech_position(&self, tech: TechType, pos: Position) -> BwResult<bool> {
+        let mut inner = self.inner.borrow_mut();
+        inner.act(
+            |_| self.unit.use_tech(tech, pos),
+            PendingGoal::UseTech(tech),
+            3,
+        )
+    }
+
+    pub fn use_tech_unit(&self, tech: TechType, target: &SUnit) -> BwResult<bool> {
+        let mut inner = self.inner.borrow_mut();
+        inner.act(
+            |_| self.unit.use_tech(tech, &target.unit),
+            PendingGoal::UseTech(tech),
+            3,
+        )
+    }
+
+    pub fn burrow(&self) -> BwResult<bool> {
+        let mut inner = self.inner.borrow_mut();
+        inner.act(
+            |inner| {
+                if inner.burrowed {
+                    Ok(true)
+                } else {
+                    self.unit.burrow()
+                }
+            },
+            PendingGoal::Burrow,
+            3,
+        )
+    }
+
+    pub fn unburrow(&self) -> BwResult<bool> {
+        let mut inner = self.inner.borrow_mut();
+        inner.act(
+            |inner| {
+                if !inner.burrowed {
+                    Ok(true)
+                } else {
+                    self.unit.unburrow()
+                }
+            },
+            PendingGoal::Unburrow,
+            3,
+        )
+    }
+
     pub fn cancel_morph(&self) -> BwResult<bool> {
         let mut inner = self.inner.borrow_mut();
         inner.pending_goal = Pending {
@@ -610,6 +698,11 @@ impl SUnit {
         inner.carrying_minerals
     }
 
+    pub fn carrying_gas(&self) -> bool {
+        let inner = self.inner.borrow();
+        inner.carrying_gas
+    }
+
     pub fn get_order(&self) -> Order {
         self.inner.borrow().order
     }
@@ -622,6 +715,10 @@ impl SUnit {
         self.inner.borrow().is_plagued
     }
 
+    pub fn parasited(&self) -> bool {
+        self.inner.borrow().is_parasited
+    }
+
     pub fn visible(&self) -> bool {
         self.inner.borrow().is_visible
     }
@@ -749,6 +846,10 @@ impl SUnit {
     pub fn attack(&self, unit: &SUnit) -> BwResult<bool> {
         let mut inner = self.inner.borrow_mut();
         let stop_frames = stop_frames(inner.type_);
+        // Moving is never worth more than shooting, otherwise we can't stutter step
+        if matches!(inner.pending_goal.goal, PendingGoal::MoveTo(_)) {
+            inner.pending_goal.goal = PendingGoal::Nothing;
+        }
         inner.act(
             |inner| {
                 if inner.order_target().as_ref() == Some(unit) {
@@ -853,6 +954,7 @@ pub struct UnitInfo {
     pub is_constructing: bool,
     pub is_visible: bool,
     pub is_plagued: bool,
+    pub is_parasited: bool,
     pub last_seen: i32,
     pub last_attack_frame: i32,
     pub last_command_frame: i32,
@@ -891,8 +993,11 @@ pub struct UnitInfo {
     pub stasis_timer: i32,
     pub lockdown_timer: i32,
     pub ensnare_timer: i32,
+    pub irradiate_timer: i32,
     pub stuck_frames: i32,
     pub mining_frames: i32,
+    // Minerals or gas left
+    pub resources: i32,
 }
 
 #[derive(Debug, Clone)]
@@ -935,6 +1040,7 @@ impl UnitInfo {
             is_constructing: unit.is_constructing(),
             is_visible: unit.is_visible(),
             is_plagued: unit.is_plagued(),
+            is_parasited: unit.is_parasited(),
             last_seen: game.get_frame_count(),
             last_attack_frame: if cooldown > 0 {
                 game.get_frame_count()
@@ -1008,8 +1114,10 @@ impl UnitInfo {
             stasis_timer: unit.get_stasis_timer(),
             lockdown_timer: unit.get_lockdown_timer(),
             ensnare_timer: unit.get_ensnare_timer(),
+            irradiate_timer: unit.get_irradiate_timer(),
             stuck_frames: 0,
             mining_frames: 0,
+            resources: unit.get_resources(),
         }
     }
 
@@ -1049,6 +1157,10 @@ impl UnitInfo {
             PendingGoal::Morph(_)
             | PendingGoal::AttackPosition(_)
             | PendingGoal::Upgrade(_)
+            | PendingGoal::Research(_)
+            | PendingGoal::UseTech(_)
+            | PendingGoal::Burrow
+            | PendingGoal::Unburrow
             | PendingGoal::MoveTo(_)
             | PendingGoal::Nothing
             | PendingGoal::Build(_)
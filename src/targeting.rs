@@ -1,22 +1,152 @@
 use crate::*;
 use ahash::AHashMap;
 use rsbwapi::*;
-use std::cell::Cell;
-use std::cmp::Ordering;
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
 use std::fmt::{Display, Error, Formatter};
 
+// Frames between an attack and the damage showing up in the target's hit points
+const HIT_DELAY_FRAMES: i32 = 8;
+// Attacks happening within this many frames count as in-flight damage
+const PROJECTION_FRAMES: i32 = 12;
+// Score lost per point of damage wasted on an already dead target
+const OVERKILL_PENALTY: i32 = 4;
+
 pub struct UnitCluster<'a> {
     pub vanguard: &'a SUnit,
     pub units: &'a [&'a SUnit],
     pub vanguard_dist_to_target: u32,
 }
 
+struct PendingHit {
+    attacker: SUnit,
+    target: SUnit,
+    damage: i32,
+    hit_frame: i32,
+    // Hit points and shields of the target when the attack was ordered
+    target_health: i32,
+}
+
+// Damage of attacks we already ordered, but which did not yet hit. Kept across frames, so
+// other attackers don't waste shots on a target that is already as good as dead.
+#[derive(Default)]
+pub struct ProjectedDamage {
+    // At most one attack per attacker
+    hits: RefCell<AHashMap<UnitId, PendingHit>>,
+}
+
+impl ProjectedDamage {
+    pub fn update(&mut self, frame: i32) {
+        // Once the target lost health, the damage is no longer pending
+        self.hits.get_mut().retain(|_, hit| {
+            hit.hit_frame >= frame
+                && hit.attacker.exists()
+                && hit.target.exists()
+                && hit.target.hit_points() + hit.target.shields() >= hit.target_health
+        });
+    }
+
+    fn pending_damage(&self, target: &SUnit) -> i32 {
+        self.hits
+            .borrow()
+            .values()
+            .filter(|hit| hit.target == *target)
+            .map(|hit| hit.damage)
+            .sum()
+    }
+
+    fn has_pending_hit(&self, attacker: &SUnit) -> bool {
+        self.hits.borrow().contains_key(&attacker.id())
+    }
+
+    fn record(&self, attacker: &SUnit, target: &SUnit, damage: i32, hit_frame: i32) {
+        self.hits.borrow_mut().insert(
+            attacker.id(),
+            PendingHit {
+                attacker: attacker.clone(),
+                target: target.clone(),
+                damage,
+                hit_frame,
+                target_health: target.hit_points() + target.shields(),
+            },
+        );
+    }
+
+    fn forget(&self, attacker: &SUnit) {
+        self.hits.borrow_mut().remove(&attacker.id());
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TargetOption {
+    // Index of the target
+    pub target: usize,
+    // How much the attacker likes this target, regardless of other attackers
+    pub score: i32,
+    pub damage: i32,
+    // Cooldown and time to get into range
+    pub frames_to_attack: i32,
+}
+
+// Assigns each attacker (given by its options) to at most one target. Greedily fixes the
+// attacker/target pair with the highest gain over all attackers, and applies its damage before
+// looking at the next pair. Killing a target gains its `kill_value`, wasted damage is penalized.
+// Attacks that won't happen soon don't reduce `health`, they can't be relied upon.
+pub fn assign_targets(
+    options: &[Vec<TargetOption>],
+    health: &mut [i32],
+    kill_value: &[i32],
+) -> Vec<Option<TargetOption>> {
+    let gain = |option: &TargetOption, health: &[i32]| {
+        let remaining = health[option.target];
+        if remaining <= 0 {
+            return None;
+        }
+        if option.frames_to_attack > PROJECTION_FRAMES {
+            return Some(option.score);
+        }
+        let overkill = 0.max(option.damage - remaining);
+        let kill = if option.damage >= remaining {
+            kill_value[option.target]
+        } else {
+            0
+        };
+        Some(option.score + kill - OVERKILL_PENALTY * overkill)
+    };
+    let mut result = vec![None; options.len()];
+    let mut unassigned: Vec<usize> = (0..options.len()).collect();
+    loop {
+        let mut best: Option<(usize, TargetOption, i32)> = None;
+        for (i, &attacker) in unassigned.iter().enumerate() {
+            for option in options[attacker].iter() {
+                let Some(value) = gain(option, health) else {
+                    continue;
+                };
+                if best
+                    .map(|(_, _, best_value)| value > best_value)
+                    .unwrap_or(true)
+                {
+                    best = Some((i, *option, value));
+                }
+            }
+        }
+        let Some((i, option, _)) = best else {
+            break;
+        };
+        let attacker = unassigned.remove(i);
+        if option.frames_to_attack <= PROJECTION_FRAMES {
+            health[option.target] -= option.damage;
+        }
+        result[attacker] = Some(option);
+    }
+    result
+}
+
 struct Target<'a> {
     unit: &'a SUnit,
     priority: i32,
+    // Including damage already on its way
     health_including_shields: Cell<i32>,
-    attacker_count: Cell<i32>,
     cliffed_tank: bool,
 }
 
@@ -31,15 +161,16 @@ impl<'a> Target<'a> {
         Self {
             unit,
             priority: module.target_priority(unit),
-            health_including_shields: Cell::new(unit.hit_points() + unit.shields()),
-            attacker_count: Cell::new(0),
+            health_including_shields: Cell::new(
+                unit.hit_points() + unit.shields() - module.projected_damage.pending_damage(unit),
+            ),
             cliffed_tank: false, // TODO
         }
     }
 
-    fn deal_damage(&self, module: &MyModule, attacker: &SUnit) {
+    fn damage_from(&self, module: &MyModule, attacker: &SUnit) -> i32 {
         if self.unit.being_healed() {
-            return;
+            return 0;
         }
 
         let mut damage = attacker.damage_to(&self.unit);
@@ -51,17 +182,19 @@ impl<'a> Target<'a> {
         {
             damage /= 2;
         }
+        damage
+    }
 
+    fn deal_damage(&self, module: &MyModule, attacker: &SUnit) {
         self.health_including_shields
-            .set(self.health_including_shields.get() - damage);
+            .set(self.health_including_shields.get() - self.damage_from(module, attacker));
     }
 }
 
 struct Attacker<'a> {
     unit: &'a SUnit,
-    targets: Vec<&'a Target<'a>>,
-    frames_to_attack: i32,
-    close_targets: Vec<&'a Target<'a>>,
+    // Index of the target and frames until we can attack it
+    targets: Vec<(usize, i32)>,
 }
 
 impl<'a> Attacker<'a> {
@@ -69,8 +202,6 @@ impl<'a> Attacker<'a> {
         Self {
             unit,
             targets: vec![],
-            frames_to_attack: i32::MAX,
-            close_targets: vec![],
         }
     }
 
@@ -227,25 +358,42 @@ impl MyModule {
         let target_is_reachable_enemy_base = !static_position
             && self.is_target_reachable_enemy_base(target_position, cluster.vanguard);
 
+        // Attacks not fired yet will be decided anew
+        for unit in cluster.units.iter() {
+            if !unit.sleeping() && unit.cooldown() == 0 {
+                self.projected_damage.forget(unit);
+            }
+        }
+
         let targets: Vec<Target> = target_units
             .iter()
             .filter(|u| u.exists())
             .map(|target_unit| Target::new(self, target_unit, cluster.vanguard))
             .collect();
 
+        let frame = self.game.get_frame_count();
         let mut attackers = Vec::with_capacity(cluster.units.len());
         let get_current_target = |unit: &SUnit| {
             unit.get_order_target()
-                .map(|tu| targets.iter().find(|t| t.unit == &tu))
+                .map(|tu| targets.iter().position(|t| t.unit == &tu))
                 .flatten()
         };
 
         for unit in cluster.units {
             if unit.sleeping() {
-                let target = get_current_target(unit);
+                let target = get_current_target(unit).map(|i| &targets[i]);
                 if let Some(target) = target {
-                    if unit.cooldown() <= self.game.get_latency_frames() + 2 {
+                    // Already accounted for if the attack was ordered in an earlier frame
+                    if unit.cooldown() <= self.game.get_latency_frames() + 2
+                        && !self.projected_damage.has_pending_hit(unit)
+                    {
                         target.deal_damage(self, unit);
+                        self.projected_damage.record(
+                            unit,
+                            target.unit,
+                            target.damage_from(self, unit),
+                            frame + unit.cooldown() + HIT_DELAY_FRAMES,
+                        );
                     }
                 } else if unit.get_order_target().map(|x| x.exists()).unwrap_or(false) {
                     cvis().log(|| {
@@ -269,7 +417,7 @@ impl MyModule {
             let mut has_non_building = false;
             let mut filtered_targets = vec![];
 
-            for target in targets.iter() {
+            for (index, target) in targets.iter().enumerate() {
                 CVIS.lock().unwrap().log_unit_frame(unit, || {
                     format!(
                         "Start filtering: {:?} {}",
@@ -361,16 +509,15 @@ impl MyModule {
                     }
                 }
                 // This is a suitable target
-                filtered_targets.push((target, dist_to_range));
+                filtered_targets.push((index, dist_to_range));
 
                 has_non_building |= target.priority > 7;
             }
 
             let mut attacker = Attacker::new(unit);
             attacker.targets.reserve(filtered_targets.len());
-            attacker.close_targets.reserve(filtered_targets.len());
-            for target_and_dist_to_range in filtered_targets.iter() {
-                let target = target_and_dist_to_range.0;
+            for &(index, dist_to_range) in filtered_targets.iter() {
+                let target = &targets[index];
 
                 // If we are targeting an enemy base, ignore outlying buildings (except static defense) unless we have a higher-priority target
                 // Rationale: When we have a non-building target, we want to consider buildings since they might be blocking us from attacking them
@@ -386,53 +533,29 @@ impl MyModule {
                     continue;
                 }
 
-                attacker.targets.push(target);
-
                 let frames_to_attack = unit.cooldown().max(
-                    (target_and_dist_to_range.1 as f64 / unit.top_speed()) as i32
+                    (dist_to_range as f64 / unit.top_speed()) as i32
                         + self.game.get_remaining_latency_frames()
                         + 2,
                 );
-                if frames_to_attack < attacker.frames_to_attack {
-                    attacker.frames_to_attack = frames_to_attack;
-                    attacker.close_targets.clear();
-                    attacker.close_targets.push(target);
-                } else if frames_to_attack == attacker.frames_to_attack {
-                    attacker.close_targets.push(target);
-                }
-            }
-
-            for close_target in attacker.close_targets.iter() {
-                close_target
-                    .attacker_count
-                    .set(close_target.attacker_count.get() + 1);
+                attacker.targets.push((index, frames_to_attack));
             }
             attackers.push(attacker);
         }
 
-        attackers.sort_by(|a, b| {
-            a.frames_to_attack
-                .cmp(&b.frames_to_attack)
-                .then_with(|| a.close_targets.len().cmp(&b.close_targets.len()))
-                .then_with(|| a.targets.len().cmp(&b.targets.len()))
-                .then_with(|| a.unit.id().cmp(&b.unit.id()))
-        });
-
-        // Now assign each unit a target, skipping any that are simulated to already be dead
-        for attacker in attackers {
+        // Score each possible target of each attacker
+        let mut options = Vec::with_capacity(attackers.len());
+        for attacker in attackers.iter() {
             let unit = &attacker.unit;
-
-            let mut best_target = None;
-            let mut best_score = -999999;
-            let mut best_attacker_count = 0;
-            let mut best_dist = i32::MAX;
+            let mut unit_options = Vec::with_capacity(attacker.targets.len());
 
             let is_ranged = unit.get_type().is_ranged();
             let cooldown_move_frames =
                 0.max(unit.cooldown() - self.game.get_remaining_latency_frames() - 2);
 
             let distance_to_target_position = unit.position().distance(target_position);
-            for potential_target in attacker.targets {
+            for &(index, frames_to_attack) in attacker.targets.iter() {
+                let potential_target = &targets[index];
                 CVIS.lock().unwrap().log_unit_frame(&attacker.unit, || {
                     format!(
                         "Start Eval: {:?} {}",
@@ -440,12 +563,6 @@ impl MyModule {
                         potential_target.unit.id(),
                     )
                 });
-                if potential_target.health_including_shields.get() <= 0 {
-                    CVIS.lock()
-                        .unwrap()
-                        .log_unit_frame(&attacker.unit, || "Health dropout".to_string());
-                    continue;
-                }
                 // Initialize the score as a formula of the target priority and how far outside our attack range it is
                 // Each priority step is equivalent to 2 tiles
                 // If the unit is on cooldown, we assume it can move towards the target before attacking
@@ -565,58 +682,139 @@ impl MyModule {
                 CVIS.lock()
                     .unwrap()
                     .log_unit_frame(&attacker.unit, || format!("Score {}", score,));
-                // See if this is the best target
-                // Criteria:
-                // - Score is higher
-                // - Attackers is higher
-                // - Distance is lower
-                if score
-                    .cmp(&best_score)
-                    .then_with(|| {
-                        potential_target
-                            .attacker_count
-                            .get()
-                            .cmp(&best_attacker_count)
-                    })
-                    .then_with(|| best_dist.cmp(&target_dist))
-                    .then(Ordering::Equal)
-                    == Ordering::Greater
-                {
-                    best_score = score;
-                    best_attacker_count = potential_target.attacker_count.get();
-                    best_dist = target_dist;
-                    best_target = Some(potential_target);
-                }
+                unit_options.push(TargetOption {
+                    target: index,
+                    score,
+                    damage: potential_target.damage_from(self, unit),
+                    frames_to_attack,
+                });
             }
 
-            CVIS.lock().unwrap().log_unit_frame(&attacker.unit, || {
-                format!(
-                    "Result {} {:?}",
-                    best_score,
-                    best_target.map(|t| (t.unit.id(), t.unit.get_type()))
-                )
-            });
             // For carriers, avoid frequently switching targets
             if unit.get_type() == UnitType::Protoss_Carrier {
-                let current_target = get_current_target(unit);
-                if let Some(current_target) = current_target {
-                    if (unit.position().distance(current_target.unit.position()) as i64) < 11 * 32
-                        && unit.last_command_frame() > self.game.get_frame_count() - 96
+                if let Some(current_target) = get_current_target(unit) {
+                    if (unit
+                        .position()
+                        .distance(targets[current_target].unit.position())
+                        as i64)
+                        < 11 * 32
+                        && unit.last_command_frame() > frame - 96
+                        && unit_options.iter().any(|o| o.target == current_target)
                     {
-                        best_target = Some(current_target)
+                        unit_options.retain(|o| o.target == current_target);
                     }
                 }
             }
-            if let Some(best_target) = best_target {
-                if unit.is_in_weapon_range(best_target.unit) {
-                    best_target.deal_damage(self, unit);
+            options.push(unit_options);
+        }
+
+        // Now assign each unit a target, skipping any that are simulated to already be dead
+        let mut health: Vec<_> = targets
+            .iter()
+            .map(|t| t.health_including_shields.get())
+            .collect();
+        let kill_value: Vec<_> = targets
+            .iter()
+            .map(|t| self.value_of(t.unit.get_type(), false))
+            .collect();
+        let assignment = assign_targets(&options, &mut health, &kill_value);
+        for (attacker, option) in attackers.iter().zip(assignment) {
+            let unit = attacker.unit;
+            CVIS.lock().unwrap().log_unit_frame(unit, || {
+                format!(
+                    "Result {:?} {:?}",
+                    option.map(|o| o.score),
+                    option.map(|o| (
+                        targets[o.target].unit.id(),
+                        targets[o.target].unit.get_type()
+                    ))
+                )
+            });
+            if let Some(option) = option {
+                let target = &targets[option.target];
+                // Attacks still on cooldown were recorded when they were ordered
+                if option.frames_to_attack <= PROJECTION_FRAMES
+                    && !self.projected_damage.has_pending_hit(unit)
+                {
+                    self.projected_damage.record(
+                        unit,
+                        target.unit,
+                        option.damage,
+                        frame + option.frames_to_attack + HIT_DELAY_FRAMES,
+                    );
                 }
-                result.push((attacker.unit.clone(), Some(best_target.unit.clone())));
+                result.push((unit.clone(), Some(target.unit.clone())));
             } else {
-                result.push((attacker.unit.clone(), None));
+                result.push((unit.clone(), None));
             }
         }
 
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(target: usize, score: i32, damage: i32, frames_to_attack: i32) -> TargetOption {
+        TargetOption {
+            target,
+            score,
+            damage,
+            frames_to_attack,
+        }
+    }
+
+    #[test]
+    fn should_not_overkill() {
+        let options = vec![
+            vec![option(0, 100, 20, 0), option(1, 90, 20, 0)],
+            vec![option(0, 100, 20, 0), option(1, 90, 20, 0)],
+        ];
+        let mut health = vec![10, 40];
+
+        let assignment = assign_targets(&options, &mut health, &[50, 50]);
+
+        assert_eq!(assignment[0].map(|o| o.target), Some(0));
+        assert_eq!(assignment[1].map(|o| o.target), Some(1));
+        assert_eq!(health, vec![-10, 20]);
+    }
+
+    #[test]
+    fn should_focus_fire_to_kill() {
+        let options = vec![
+            vec![option(0, 100, 10, 0), option(1, 100, 10, 0)],
+            vec![option(0, 100, 10, 0), option(1, 100, 10, 0)],
+        ];
+        let mut health = vec![20, 20];
+
+        let assignment = assign_targets(&options, &mut health, &[50, 50]);
+
+        assert_eq!(assignment[0].map(|o| o.target), Some(0));
+        assert_eq!(assignment[1].map(|o| o.target), Some(0));
+        assert_eq!(health, vec![0, 20]);
+    }
+
+    #[test]
+    fn should_not_count_distant_attacks_as_damage() {
+        let options = vec![vec![option(0, 100, 20, 100)], vec![option(0, 50, 20, 0)]];
+        let mut health = vec![20];
+
+        let assignment = assign_targets(&options, &mut health, &[50]);
+
+        assert_eq!(assignment[0].map(|o| o.target), Some(0));
+        assert_eq!(assignment[1].map(|o| o.target), Some(0));
+        assert_eq!(health, vec![0]);
+    }
+
+    #[test]
+    fn should_ignore_dead_targets() {
+        let options = vec![vec![option(0, 100, 20, 0)]];
+        let mut health = vec![0];
+
+        let assignment = assign_targets(&options, &mut health, &[50]);
+
+        assert_eq!(assignment, vec![None]);
+    }
+}
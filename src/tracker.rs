@@ -1,11 +1,12 @@
 use crate::gms::*;
 use crate::sunit::*;
-use crate::{UnitId, UnitType, UpgradeType};
+use crate::{TechType, UnitId, UnitType, UpgradeType};
 
 #[derive(Debug)]
 pub enum UnrealizedItem {
     UnitType(Gms, UnitType),
     Upgrade(Gms, UpgradeType),
+    Tech(Gms, TechType),
 }
 
 #[derive(Debug, Default)]
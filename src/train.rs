@@ -44,10 +44,12 @@ impl MyModule {
 
     pub fn pump(&mut self, unit_type: UnitType) -> Result<(), FailureReason> {
         let trainers = self.count_completed(|ut| ut == unit_type.what_builds().0);
-        for _ in 0..trainers {
-            self.start_train(TrainParam::train(unit_type))?;
-        }
-        Ok(())
+        self.with_priority(ARMY_PRIORITY, |module| {
+            for _ in 0..trainers {
+                module.start_train(TrainParam::train(unit_type))?;
+            }
+            Ok(())
+        })
     }
 
     pub fn start_train(&mut self, param: TrainParam) -> Result<(), FailureReason> {
@@ -56,17 +58,24 @@ impl MyModule {
             "{:?} cannot be trained",
             param.unit_type
         );
+        self.claim_production(ProductionItem::Unit(param.unit_type));
         assert!(
             param.unit_type.price().gas <= 0.max(self.tracker.available_gms.gas)
                 || self.has_pending_ready_or_planned(|ut| ut.is_refinery()),
             "Not enough gas to build {:?}, and no refinery planned or built!",
             param.unit_type
         );
-        if !self
-            .tracker
-            .available_gms
-            .checked_sub(param.unit_type.price())
-        {
+        let trainer_type = param.unit_type.what_builds().0;
+        // Lurkers, Guardians and Devourers are morphed from existing units instead of larva
+        let morph = trainer_type.get_race() == Race::Zerg
+            && trainer_type != UnitType::Zerg_Larva
+            && !trainer_type.is_building();
+        let mut price = param.unit_type.price();
+        if morph {
+            // The supply of the morphing unit is already used
+            price.supply -= trainer_type.supply_required();
+        }
+        if !self.tracker.available_gms.checked_sub(price) {
             self.tracker.unrealized.push(UnrealizedItem::UnitType(
                 self.tracker.available_gms,
                 param.unit_type,
@@ -74,15 +83,41 @@ impl MyModule {
             return Err(FailureReason::InsufficientResources);
         }
 
+        if morph {
+            if !self.has_requirements_for(param.unit_type) {
+                return Err(FailureReason::misc("Requirements for morph missing"));
+            }
+            let trainer = self
+                .tracker
+                .available_units
+                .iter()
+                // Don't morph units in the middle of a fight, they'd be helpless for a while
+                .filter(|u| {
+                    u.get_type() == trainer_type
+                        && !u.attacking()
+                        && self.grids.threat_at(u.position(), u.flying()) <= 0.0
+                })
+                .min_by_key(|u| !u.idle())
+                .ok_or(FailureReason::misc("No unit to morph found"))?
+                .clone();
+            self.tracker.reserve_unit(&trainer);
+            self.assign_production_builder(&trainer);
+            return trainer
+                .morph(param.unit_type)
+                .map(|_| ())
+                .map_err(|code| FailureReason::Bwapi(code));
+        }
+
         let trainer = self
             .tracker
             .available_units
             .iter()
-            .filter(|u| u.idle() && u.get_type() == param.unit_type.what_builds().0)
+            .filter(|u| u.idle() && u.get_type() == trainer_type)
             .next()
             .ok_or(FailureReason::misc("No trainer found"))?
             .id();
         let trainer = self.tracker.reserve_unit(trainer);
+        self.assign_production_builder(&trainer);
         trainer
             .train(param.unit_type)
             .map(|_| ())
@@ -0,0 +1,233 @@
+use crate::*;
+
+// Don't bother walking across the map for less
+const MIN_BATCH: usize = 4;
+// Drones closer than this to their new base have arrived
+const ARRIVAL_RADIUS: f64 = 8.0 * 32.0;
+// Enemy army units seen near the path within this many frames make it unsafe
+const THREAT_FRAMES: i32 = 24 * 20;
+const THREAT_RADIUS: f64 = 8.0 * 32.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BaseSaturation {
+    pub workers: usize,
+    pub capacity: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: usize,
+    pub to: usize,
+    pub count: usize,
+}
+
+// Move drones from over saturated (or mined out) bases to the closest bases with free mineral
+// fields. `distance` is `None` if drones can't safely walk between two bases.
+pub fn plan_transfers(
+    bases: &[BaseSaturation],
+    distance: impl Fn(usize, usize) -> Option<u32>,
+) -> Vec<Transfer> {
+    let mut surplus: Vec<_> = bases
+        .iter()
+        .map(|b| b.workers.saturating_sub(b.capacity))
+        .collect();
+    let mut deficit: Vec<_> = bases
+        .iter()
+        .map(|b| b.capacity.saturating_sub(b.workers))
+        .collect();
+    let mut sources: Vec<_> = (0..bases.len()).filter(|&i| surplus[i] > 0).collect();
+    sources.sort_by_key(|&i| std::cmp::Reverse(surplus[i]));
+    let mut result = vec![];
+    for from in sources {
+        let mut destinations: Vec<_> = (0..bases.len())
+            .filter(|&to| to != from && deficit[to] > 0)
+            .filter_map(|to| distance(from, to).map(|d| (to, d)))
+            .collect();
+        destinations.sort_by_key(|(_, d)| *d);
+        for (to, _) in destinations {
+            let count = surplus[from].min(deficit[to]);
+            // Mined out bases send everyone, even single drones
+            if count < MIN_BATCH && bases[from].capacity > 0 {
+                continue;
+            }
+            surplus[from] -= count;
+            deficit[to] -= count;
+            result.push(Transfer { from, to, count });
+        }
+    }
+    result
+}
+
+#[derive(Default)]
+pub struct WorkerTransfers {
+    // Drones on their way and the base they're going to
+    pub in_transit: Vec<(SUnit, TilePosition)>,
+}
+
+impl MyModule {
+    fn is_transfer_path_safe(&self, from: Position, to: Position) -> bool {
+        let frame = self.game.get_frame_count();
+        let (chokes, _) = self.map.get_path(from, to);
+        let waypoints: Vec<_> = chokes
+            .iter()
+            .map(|cp| cp.top.center())
+            .chain(std::iter::once(to))
+            .collect();
+        !self.units.enemy.iter().any(|e| {
+            frame - e.last_seen() < THREAT_FRAMES
+                && !e.get_type().is_worker()
+                && e.get_type().can_attack()
+                && waypoints
+                    .iter()
+                    .any(|w| w.distance(e.position()) < THREAT_RADIUS)
+        })
+    }
+
+    // Moves excess and idle drones to bases with free mineral fields, one batch at a time. They
+    // walk a safe path and only start mining once they arrived.
+    pub fn perform_worker_transfers(&mut self) {
+        let in_transit = std::mem::take(&mut self.transfers.in_transit);
+        for (drone, target) in in_transit {
+            if !drone.exists() || self.tracker.try_reserve_unit(&drone).is_none() {
+                continue;
+            }
+            if drone.position().distance(target.center()) < ARRIVAL_RADIUS {
+                // Arrived, start mining at the new base
+                if let Some(mineral) = self
+                    .units
+                    .minerals
+                    .iter()
+                    .filter(|m| m.tile_position().distance_squared(target) < 100)
+                    .min_by_key(|m| m.being_gathered())
+                {
+                    drone.gather(mineral).ok();
+                }
+                continue;
+            }
+            // Don't walk past enemies on the way
+            self.safe_move_to(&drone, target.center());
+            self.transfers.in_transit.push((drone, target));
+        }
+        if !self.transfers.in_transit.is_empty() {
+            for (drone, target) in self.transfers.in_transit.iter() {
+                let p = drone.position();
+                let t = target.center();
+                cvis().draw_line(p.x, p.y, t.x, t.y, Color::Cyan);
+            }
+            return;
+        }
+
+        let bases: Vec<_> = self
+            .bases
+            .all()
+            .filter(|b| {
+                b.resource_depot
+                    .as_ref()
+                    .map(|d| d.player().is_me() && d.completed())
+                    .unwrap_or(false)
+            })
+            .collect();
+        let saturation: Vec<_> = bases
+            .iter()
+            .map(|b| BaseSaturation {
+                workers: b.workers,
+                capacity: b.worker_capacity(),
+            })
+            .collect();
+        let transfers = plan_transfers(&saturation, |from, to| {
+            let (a, b) = (bases[from].position.center(), bases[to].position.center());
+            Some(self.map.get_path(a, b).1).filter(|_| self.is_transfer_path_safe(a, b))
+        });
+        let transfers: Vec<_> = transfers
+            .iter()
+            .map(|t| (bases[t.from].position, bases[t.to].position, t.count))
+            .collect();
+
+        for (from, to, count) in transfers {
+            let mut drones: Vec<_> = self
+                .tracker
+                .available_units
+                .iter()
+                .filter(|u| {
+                    u.get_type().is_worker()
+                        && (u.gathering_minerals() || u.idle())
+                        && u.tile_position().distance_squared(from) < 100
+                })
+                .cloned()
+                .collect();
+            // Let drones carrying minerals return them first
+            drones.sort_by_key(|u| (u.carrying(), u.position().distance(to.center()) as i32));
+            if !self
+                .units
+                .minerals
+                .iter()
+                .any(|m| m.tile_position().distance_squared(to) < 100)
+            {
+                continue;
+            }
+            cvis().log(|| {
+                format!(
+                    "Transferring {} drones from {} to {}",
+                    count.min(drones.len()),
+                    from,
+                    to
+                )
+            });
+            for drone in drones.iter().take(count) {
+                self.safe_move_to(drone, to.center());
+                self.tracker.reserve_unit(drone);
+                self.transfers.in_transit.push((drone.clone(), to));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(workers: usize, capacity: usize) -> BaseSaturation {
+        BaseSaturation { workers, capacity }
+    }
+
+    #[test]
+    fn should_send_surplus_to_closest_base() {
+        let bases = [base(24, 16), base(0, 16), base(0, 16)];
+
+        let transfers = plan_transfers(&bases, |_, to| Some(to as u32 * 100));
+
+        assert_eq!(
+            transfers,
+            vec![Transfer {
+                from: 0,
+                to: 1,
+                count: 8
+            }]
+        );
+    }
+
+    #[test]
+    fn should_not_transfer_small_batches_or_along_unsafe_paths() {
+        let bases = [base(18, 16), base(0, 16)];
+        assert!(plan_transfers(&bases, |_, _| Some(100)).is_empty());
+
+        let bases = [base(24, 16), base(0, 16)];
+        assert!(plan_transfers(&bases, |_, _| None).is_empty());
+    }
+
+    #[test]
+    fn should_evacuate_mined_out_base() {
+        let bases = [base(2, 0), base(10, 16), base(14, 16)];
+
+        let transfers = plan_transfers(&bases, |_, to| Some(to as u32 * 100));
+
+        assert_eq!(
+            transfers,
+            vec![Transfer {
+                from: 0,
+                to: 1,
+                count: 2
+            }]
+        );
+    }
+}
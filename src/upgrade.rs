@@ -26,6 +26,7 @@ impl MyModule {
                 .expect("Self must exist")
                 .get_upgrade_level(upgrade),
         );
+        self.claim_production(ProductionItem::Upgrade(upgrade));
         if !self.tracker.available_gms.checked_sub(price) {
             self.tracker
                 .unrealized
@@ -42,7 +43,45 @@ impl MyModule {
             .next()
             .ok_or(FailureReason::misc("No upgrader found"))?;
         self.tracker.reserve_unit(&researcher);
+        self.assign_production_builder(&researcher);
         researcher.upgrade(upgrade).map_err(FailureReason::Bwapi)?;
         Ok(())
     }
+
+    pub fn has_pending_or_researched(&self, tech: TechType) -> bool {
+        let self_ = self.game.self_().unwrap();
+        self_.has_researched(tech) || self_.is_researching(tech)
+    }
+
+    pub fn ensure_tech(&mut self, tech: TechType) -> Result<(), FailureReason> {
+        if self.has_pending_or_researched(tech) {
+            Ok(())
+        } else {
+            self.start_research(tech)
+        }
+    }
+
+    pub fn start_research(&mut self, tech: TechType) -> Result<(), FailureReason> {
+        let price = tech.price();
+        self.claim_production(ProductionItem::Tech(tech));
+        if !self.tracker.available_gms.checked_sub(price) {
+            self.tracker
+                .unrealized
+                .push(UnrealizedItem::Tech(self.tracker.available_gms, tech));
+            return Err(FailureReason::InsufficientResources);
+        }
+
+        let researcher = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| u.get_type() == tech.what_researches() && u.completed())
+            .cloned()
+            .next()
+            .ok_or(FailureReason::misc("No researcher found"))?;
+        self.tracker.reserve_unit(&researcher);
+        self.assign_production_builder(&researcher);
+        researcher.research(tech).map_err(FailureReason::Bwapi)?;
+        Ok(())
+    }
 }
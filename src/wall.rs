@@ -0,0 +1,495 @@
+use crate::build::BuildParam;
+use crate::*;
+
+// Half the width of the widest choke we try to wall off
+const MAX_CHOKE_HALF_WIDTH: i32 = 12;
+const MAX_EVOS: usize = 3;
+const MAX_SUNKENS: usize = 2;
+// Only if the natural hatchery itself isn't part of the wall
+const MAX_MACRO_HATCHERIES: usize = 1;
+// Don't search forever for walls that can't be built
+const MAX_SEARCH_NODES: usize = 5000;
+// Sunkens not needed for the wall are placed inside, but not right next to the gap
+const MIN_SUNKEN_GAP_DISTANCE: f64 = 2.0 * 32.0;
+const MAX_SUNKEN_GAP_DISTANCE: f64 = 6.0 * 32.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WallPlan {
+    // Top left tile of the natural hatchery, if it is part of the wall
+    pub hatchery: Option<TilePosition>,
+    // Evolution Chambers, sunkens and a macro hatchery with their top left tiles
+    pub buildings: Vec<(UnitType, TilePosition)>,
+    // The one tile left open
+    pub gap: TilePosition,
+}
+
+#[derive(Default)]
+pub struct Wall {
+    planned: bool,
+    // The plan was made knowing where the enemy base is
+    planned_for_enemy_base: bool,
+    pub plan: Option<WallPlan>,
+}
+
+fn footprint(unit_type: UnitType, tl: TilePosition) -> impl Iterator<Item = TilePosition> {
+    let size = unit_type.tile_size();
+    (0..size.x)
+        .flat_map(move |dx| (0..size.y).map(move |dy| TilePosition::new(tl.x + dx, tl.y + dy)))
+}
+
+struct WallSearch<'a, F: Fn(&TilePosition) -> bool> {
+    line: &'a [TilePosition],
+    free: F,
+    blocked: Vec<TilePosition>,
+    buildings: Vec<(UnitType, TilePosition)>,
+    // Buildings we may use, and how many of each
+    pieces: [(UnitType, usize); 3],
+    nodes: usize,
+    // Direction "inside", to prefer buildings behind the choke
+    inward: (f64, f64),
+}
+
+impl<F: Fn(&TilePosition) -> bool> WallSearch<'_, F> {
+    // Cover all open tiles of the line, one building at a time
+    fn cover(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes > MAX_SEARCH_NODES {
+            return false;
+        }
+        let open: Vec<_> = self
+            .line
+            .iter()
+            .filter(|t| !self.blocked.contains(t))
+            .copied()
+            .collect();
+        let Some(&frontier) = open.first() else {
+            return true;
+        };
+        let remaining: Vec<_> = self
+            .pieces
+            .iter()
+            .map(|&(unit_type, max)| {
+                let used = self
+                    .buildings
+                    .iter()
+                    .filter(|(ut, _)| *ut == unit_type)
+                    .count();
+                (unit_type, max - used)
+            })
+            .collect();
+        // Not enough buildings left, even if all of them were placed along the line
+        let capacity: usize = remaining
+            .iter()
+            .map(|(ut, left)| {
+                let size = ut.tile_size();
+                left * size.x.max(size.y) as usize
+            })
+            .sum();
+        if capacity < open.len() {
+            return false;
+        }
+        for (unit_type, left) in remaining {
+            if left == 0 {
+                continue;
+            }
+            let size = unit_type.tile_size();
+            let mut candidates: Vec<_> = (0..size.x)
+                .flat_map(|dx| {
+                    (0..size.y).map(move |dy| TilePosition::new(frontier.x - dx, frontier.y - dy))
+                })
+                .filter(|tl| {
+                    footprint(unit_type, *tl).all(|t| (self.free)(&t) && !self.blocked.contains(&t))
+                })
+                .collect();
+            candidates.sort_by_key(|tl| {
+                let covered = footprint(unit_type, *tl)
+                    .filter(|t| open.contains(t))
+                    .count() as i32;
+                let inside: f64 = footprint(unit_type, *tl)
+                    .map(|t| {
+                        (t.x - frontier.x) as f64 * self.inward.0
+                            + (t.y - frontier.y) as f64 * self.inward.1
+                    })
+                    .sum();
+                (-covered, -inside as i32)
+            });
+            for tl in candidates {
+                let blocked = self.blocked.len();
+                self.blocked.extend(footprint(unit_type, tl));
+                self.buildings.push((unit_type, tl));
+                if self.cover() {
+                    return true;
+                }
+                self.buildings.pop();
+                self.blocked.truncate(blocked);
+            }
+        }
+        false
+    }
+}
+
+// Walls off the choke cross section through `center`. `outward` points from our natural to the
+// outside. All tiles of the cross section but the gap are covered by buildings, remaining sunkens
+// go on the inside behind the gap.
+pub fn plan_wall(
+    center: TilePosition,
+    outward: (f64, f64),
+    hatchery: Option<TilePosition>,
+    walkable: impl Fn(TilePosition) -> bool,
+    buildable: impl Fn(TilePosition) -> bool,
+) -> Option<WallPlan> {
+    let length = (outward.0 * outward.0 + outward.1 * outward.1).sqrt();
+    if length == 0.0 {
+        return None;
+    }
+    let (ox, oy) = (outward.0 / length, outward.1 / length);
+    let along = |t: f64| {
+        TilePosition::new(
+            center.x + (-oy * t).round() as i32,
+            center.y + (ox * t).round() as i32,
+        )
+    };
+    let mut line = vec![];
+    for side in [-1.0, 1.0] {
+        for t in (0..=MAX_CHOKE_HALF_WIDTH).map(|t| t as f64 * side) {
+            let tile = along(t);
+            if !walkable(tile) {
+                break;
+            }
+            if !line.contains(&tile) {
+                line.push(tile);
+            }
+        }
+    }
+    if line.len() < 2 {
+        return None;
+    }
+    line.sort_by_key(|t| {
+        (((t.x - center.x) as f64 * -oy + (t.y - center.y) as f64 * ox) * 100.0) as i32
+    });
+    let step = |t: TilePosition, d: f64| {
+        TilePosition::new(t.x + (ox * d).round() as i32, t.y + (oy * d).round() as i32)
+    };
+    let natural: Vec<_> = hatchery
+        .iter()
+        .flat_map(|h| footprint(UnitType::Zerg_Hatchery, *h))
+        .collect();
+    let middle = line.len() / 2;
+    let mut gaps = line.clone();
+    gaps.sort_by_key(|t| (line.iter().position(|l| l == t).unwrap() as i32 - middle as i32).abs());
+
+    for gap in gaps.into_iter().filter(|g| !natural.contains(g)) {
+        // Units need to get in and out of the gap
+        let gap_lane = [step(gap, -1.0), gap, step(gap, 1.0)];
+        let wall_line: Vec<_> = line.iter().copied().filter(|t| *t != gap).collect();
+        let mut search = WallSearch {
+            line: &wall_line,
+            free: |t: &TilePosition| buildable(*t) && !gap_lane.contains(t),
+            blocked: natural.clone(),
+            buildings: vec![],
+            pieces: [
+                (UnitType::Zerg_Evolution_Chamber, MAX_EVOS),
+                (UnitType::Zerg_Sunken_Colony, MAX_SUNKENS),
+                (
+                    UnitType::Zerg_Hatchery,
+                    if hatchery.is_some() {
+                        0
+                    } else {
+                        MAX_MACRO_HATCHERIES
+                    },
+                ),
+            ],
+            nodes: 0,
+            inward: (-ox, -oy),
+        };
+        if !search.cover() {
+            continue;
+        }
+        let mut buildings = search.buildings;
+        let mut blocked = search.blocked;
+
+        let gap_center = gap.center();
+        let inside = |t: TilePosition| (t.x - gap.x) as f64 * ox + (t.y - gap.y) as f64 * oy < -1.0;
+        let sunken_distance = |tl: &TilePosition| {
+            (*tl + TilePosition::new(1, 1))
+                .to_position()
+                .distance(gap_center)
+        };
+        let mut candidates: Vec<_> = (-8..=8)
+            .flat_map(|dx| (-8..=8).map(move |dy| TilePosition::new(gap.x + dx, gap.y + dy)))
+            .filter(|tl| {
+                let distance = sunken_distance(tl);
+                distance >= MIN_SUNKEN_GAP_DISTANCE && distance <= MAX_SUNKEN_GAP_DISTANCE
+            })
+            .collect();
+        candidates.sort_by_key(|tl| sunken_distance(tl) as i32);
+        for tl in candidates {
+            if buildings
+                .iter()
+                .filter(|(ut, _)| *ut == UnitType::Zerg_Sunken_Colony)
+                .count()
+                >= MAX_SUNKENS
+            {
+                break;
+            }
+            if footprint(UnitType::Zerg_Sunken_Colony, tl).all(|t| {
+                inside(t) && buildable(t) && !gap_lane.contains(&t) && !blocked.contains(&t)
+            }) {
+                blocked.extend(footprint(UnitType::Zerg_Sunken_Colony, tl));
+                buildings.push((UnitType::Zerg_Sunken_Colony, tl));
+            }
+        }
+        return Some(WallPlan {
+            hatchery,
+            buildings,
+            gap,
+        });
+    }
+    None
+}
+
+impl MyModule {
+    // Base closest to our main, which isn't our main
+    pub fn natural(&self) -> Option<TilePosition> {
+        let main = self.game.self_()?.get_start_location();
+        self.bases
+            .all()
+            .filter(|b| b.position.distance_squared(main) >= 25)
+            .min_by_key(|b| self.map.get_path(main.center(), b.position.center()).1)
+            .map(|b| b.position)
+    }
+
+    fn plan_zvp_wall(&self) -> Option<WallPlan> {
+        let main = self.game.self_()?.get_start_location();
+        let natural = self.natural()?;
+        // The enemy base if we found it, otherwise every start location he might be at
+        let enemies: Vec<_> = self
+            .bases
+            .all()
+            .filter(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false))
+            .map(|b| b.position)
+            .collect();
+        let enemies = if enemies.is_empty() {
+            self.game
+                .get_start_locations()
+                .iter()
+                .copied()
+                .filter(|l| *l != main)
+                .collect()
+        } else {
+            enemies
+        };
+        let (inner, _) = self.map.get_path(main.center(), natural.center());
+        // Outgoing choke is the first one on the way out which we didn't pass on the way in. The
+        // one most paths to the enemy go through wins.
+        let mut chokes: Vec<(Position, usize)> = vec![];
+        for enemy in enemies {
+            let (outer, _) = self.map.get_path(natural.center(), enemy.center());
+            let Some(choke) = outer
+                .iter()
+                .map(|cp| cp.top.center())
+                .find(|c| !inner.iter().any(|i| i.top.center() == *c))
+            else {
+                continue;
+            };
+            match chokes.iter_mut().find(|(c, _)| *c == choke) {
+                Some((_, count)) => *count += 1,
+                None => chokes.push((choke, 1)),
+            }
+        }
+        let (choke, _) = chokes.into_iter().max_by_key(|(_, count)| *count)?;
+        let natural_center = natural.center();
+        let outward = (
+            (choke.x - natural_center.x) as f64,
+            (choke.y - natural_center.y) as f64,
+        );
+        plan_wall(
+            choke.to_tile_position(),
+            outward,
+            Some(natural).filter(|n| n.center().distance(choke) < 10.0 * 32.0),
+            |t| t.is_valid(&&self.game) && self.game.is_walkable(t.center().to_walk_position()),
+            |t| t.is_valid(&&self.game) && self.game.is_buildable(t),
+        )
+    }
+
+    // Existing or started building of the given type with its top left at `tile`. Drones move to
+    // the center of the building, so anything within the footprint counts.
+    fn wall_building_at(
+        &self,
+        tile: TilePosition,
+        unit_type: UnitType,
+        check: impl Fn(UnitType) -> bool,
+    ) -> bool {
+        let in_footprint = |p: TilePosition| footprint(unit_type, tile).any(|t| t == p);
+        self.units.mine_all.iter().any(|u| {
+            check(u.future_type()) && u.get_type().is_building() && u.tile_position() == tile
+                || u.build_type() != UnitType::None
+                    && check(u.build_type())
+                    && u.target_position()
+                        .map(|p| in_footprint(p.to_tile_position()))
+                        .unwrap_or(false)
+        })
+    }
+
+    // Natural hatchery, sunkens and Evolution Chambers walling off the natural with a single gap
+    pub fn ensure_zvp_wall(&mut self) -> Result<(), FailureReason> {
+        let enemy_base_known = self
+            .bases
+            .all()
+            .any(|b| b.player.as_ref().map(|p| p.is_enemy()).unwrap_or(false));
+        // A wall planned while guessing the enemy base is replanned once we found it, unless we
+        // already started building it
+        let started = self.wall.plan.as_ref().map_or(false, |plan| {
+            plan.buildings
+                .iter()
+                .any(|(unit_type, tile)| self.wall_building_at(*tile, *unit_type, |_| true))
+        });
+        if !self.wall.planned || enemy_base_known && !self.wall.planned_for_enemy_base && !started {
+            self.wall.planned = true;
+            self.wall.planned_for_enemy_base = enemy_base_known;
+            self.wall.plan = self.plan_zvp_wall();
+            if let Some(plan) = &self.wall.plan {
+                cvis().log(|| format!("ZvP wall planned: {:?}", plan));
+            }
+        }
+        let Some(plan) = self.wall.plan.clone() else {
+            return Err(FailureReason::misc("No wall found at the natural"));
+        };
+        let gap = plan.gap.center();
+        cvis().draw_circle(gap.x, gap.y, 16, Color::Green);
+        let mut buildings: Vec<_> = plan
+            .hatchery
+            .map(|h| (UnitType::Zerg_Hatchery, h))
+            .into_iter()
+            .chain(plan.buildings.iter().copied())
+            .collect();
+        // Hatcheries and sunkens first, their creep is needed for the Evolution Chambers
+        buildings.sort_by_key(|(ut, _)| *ut == UnitType::Zerg_Evolution_Chamber);
+        // One piece failing (ie. no creep yet) shouldn't stop the others
+        let mut result = Ok(());
+        for (unit_type, tile) in buildings {
+            if self.wall_building_at(tile, unit_type, |ut| ut.is_successor_of(unit_type)) {
+                continue;
+            }
+            let has_creep = |unit_type: UnitType| {
+                !unit_type.requires_creep()
+                    || footprint(unit_type, tile).all(|t| self.game.has_creep(t))
+            };
+            let piece = if unit_type == UnitType::Zerg_Sunken_Colony {
+                let colony = self.units.my_completed.iter().any(|u| {
+                    u.tile_position() == tile && u.get_type() == UnitType::Zerg_Creep_Colony
+                });
+                if colony {
+                    Some(unit_type)
+                } else if !self.wall_building_at(tile, UnitType::Zerg_Creep_Colony, |ut| {
+                    ut == UnitType::Zerg_Creep_Colony
+                }) {
+                    Some(UnitType::Zerg_Creep_Colony)
+                } else {
+                    None
+                }
+            } else {
+                Some(unit_type)
+            };
+            let Some(piece) = piece.filter(|&piece| has_creep(piece)) else {
+                continue;
+            };
+            if let Err(err) = self.start_build(BuildParam::build(piece).exactly_at(tile)) {
+                cvis().log(|| format!("Wall: {:?} at {} failed: {:?}", piece, tile, err));
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Open field with a vertical wall at x = 20, open between y = 10 and y = 15
+    fn walkable(t: TilePosition) -> bool {
+        t.x >= 0 && t.y >= 0 && t.x < 40 && t.y < 40 && (t.x != 20 || t.y >= 10 && t.y < 16)
+    }
+
+    #[test]
+    fn should_leave_single_gap() {
+        let plan = plan_wall(
+            TilePosition::new(20, 12),
+            (1.0, 0.0),
+            None,
+            walkable,
+            walkable,
+        )
+        .unwrap();
+
+        let covered: Vec<_> = plan
+            .buildings
+            .iter()
+            .flat_map(|(ut, tl)| footprint(*ut, *tl))
+            .collect();
+        let open: Vec<_> = (10..16)
+            .map(|y| TilePosition::new(20, y))
+            .filter(|t| !covered.contains(t))
+            .collect();
+        assert_eq!(open, vec![plan.gap]);
+    }
+
+    #[test]
+    fn should_use_natural_hatchery_in_wall() {
+        let hatchery = TilePosition::new(17, 10);
+
+        let plan = plan_wall(
+            TilePosition::new(20, 12),
+            (1.0, 0.0),
+            Some(hatchery),
+            walkable,
+            walkable,
+        )
+        .unwrap();
+
+        assert!(plan
+            .buildings
+            .iter()
+            .all(|(ut, _)| *ut != UnitType::Zerg_Hatchery));
+        assert!(!footprint(UnitType::Zerg_Hatchery, hatchery).any(|t| t == plan.gap));
+    }
+
+    #[test]
+    fn sunkens_should_be_inside() {
+        let plan = plan_wall(
+            TilePosition::new(20, 12),
+            (1.0, 0.0),
+            None,
+            walkable,
+            walkable,
+        )
+        .unwrap();
+
+        let sunkens: Vec<_> = plan
+            .buildings
+            .iter()
+            .filter(|(ut, _)| *ut == UnitType::Zerg_Sunken_Colony)
+            .collect();
+        assert_eq!(sunkens.len(), MAX_SUNKENS);
+        for (_, sunken) in sunkens {
+            assert!(sunken.x + 1 <= 20);
+        }
+    }
+
+    #[test]
+    fn should_fail_on_wide_open_field() {
+        assert_eq!(
+            plan_wall(
+                TilePosition::new(20, 20),
+                (1.0, 0.0),
+                None,
+                |t| t.x >= 0 && t.y >= 0 && t.x < 60 && t.y < 60,
+                |_| true,
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::combat_sim::*;
+use crate::*;
+
+// Drones with less hit points keep mining, they would just die
+const MIN_DEFENDER_HIT_POINTS: i32 = 20;
+// Enemies closer than this to one of our depots are considered attacking the mineral line
+const MINERAL_LINE_RADIUS: f64 = 10.0 * 32.0;
+// The drones required are simulated again after this many frames, or if the threats change
+const SEARCH_INTERVAL_FRAMES: i32 = 24;
+
+#[derive(Default)]
+pub struct WorkerDefense {
+    pub pulled: Vec<SUnit>,
+    // Result of the last search and what it was based on
+    threats: Vec<SUnit>,
+    required: Option<usize>,
+    searched_frame: i32,
+}
+
+impl MyModule {
+    // Find the minimum number of drones (in the given order) to pull to hold the mineral line.
+    // Not pulled drones are still simulated, but keep mining.
+    fn drones_required_to_hold(
+        &self,
+        threats: &[SUnit],
+        defenders: &[SUnit],
+        drones: &[SUnit],
+        max_pulled: usize,
+    ) -> Option<usize> {
+        let walkability = |x, y| {
+            Position::new(x, y).is_valid(&&self.game) && self.game.is_walkable((x / 8, y / 8))
+        };
+        let enemies: Vec<_> = threats.iter().map(Agent::from_unit).collect();
+        let holds = |pulled: usize| {
+            let mut agents: Vec<_> = defenders.iter().map(Agent::from_unit).collect();
+            agents.extend(drones.iter().enumerate().map(|(i, d)| {
+                // Mining drones are asleep in the sim, the pulled ones have to wake up
+                let mut agent = Agent::from_unit(d);
+                if i < pulled {
+                    agent.sleep_timer = 0;
+                }
+                agent
+            }));
+            let mut sim = Simulator {
+                player_a: Player {
+                    agents,
+                    script: Attacker::new(),
+                },
+                player_b: Player {
+                    agents: enemies.clone(),
+                    script: Attacker::new(),
+                },
+                walkability,
+            };
+            sim.simulate_for(6 * 24);
+            let my_dead: i32 = sim
+                .player_a
+                .agents
+                .iter()
+                .filter(|a| !a.is_alive)
+                .map(|a| self.value_of(a.unit_type, true))
+                .sum();
+            let enemy_dead: i32 = sim
+                .player_b
+                .agents
+                .iter()
+                .filter(|a| !a.is_alive)
+                .map(|a| self.value_of(a.unit_type, false))
+                .sum();
+            my_dead == 0 || my_dead <= enemy_dead
+        };
+        // Pulling more drones never hurts, so search for the smallest number that holds
+        let (mut low, mut high) = (0, max_pulled);
+        if !holds(high) {
+            return None;
+        }
+        while low < high {
+            let mid = (low + high) / 2;
+            if holds(mid) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Some(low)
+    }
+
+    fn release_pulled_workers(&mut self) {
+        for worker in self.worker_defense.pulled.drain(..) {
+            if worker.exists() {
+                cvis().log_unit_frame(&worker, || "Back to mining");
+                // Clear the attack, the gathering code will pick it up again
+                worker.stop().ok();
+            }
+        }
+    }
+
+    // Pull drones against early rushes or worker rushes, if our army can't hold on its own
+    pub fn perform_worker_defense(&mut self) {
+        let depots: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| u.get_type().is_resource_depot())
+            .cloned()
+            .collect();
+        let threats: Vec<_> = self
+            .units
+            .enemy
+            .iter()
+            .filter(|e| {
+                e.exists()
+                    && !e.flying()
+                    && !e.get_type().is_building()
+                    && e.get_ground_weapon().weapon_type != WeaponType::None
+                    && depots
+                        .iter()
+                        .any(|d| d.position().distance(e.position()) < MINERAL_LINE_RADIUS)
+            })
+            .cloned()
+            .collect();
+        if threats.is_empty() {
+            self.worker_defense.threats.clear();
+            self.release_pulled_workers();
+            return;
+        }
+        let n = threats.len() as i32;
+        let center = Position::new(
+            threats.iter().map(|e| e.position().x).sum::<i32>() / n,
+            threats.iter().map(|e| e.position().y).sum::<i32>() / n,
+        );
+        let defenders: Vec<_> = self
+            .units
+            .my_completed
+            .iter()
+            .filter(|u| {
+                is_attacker(u) && u.position().distance(center) < MINERAL_LINE_RADIUS + 64.0
+            })
+            .cloned()
+            .collect();
+        let mut drones: Vec<_> = self
+            .tracker
+            .available_units
+            .iter()
+            .filter(|u| {
+                u.get_type().is_worker()
+                    && u.position().distance(center) < MINERAL_LINE_RADIUS + 64.0
+            })
+            .cloned()
+            .collect();
+        // Keep already pulled drones fighting to avoid toggling, then prefer healthy ones. Drones
+        // low on hit points are sorted last and will never be pulled.
+        drones.sort_by_key(|w| {
+            (
+                w.hit_points() < MIN_DEFENDER_HIT_POINTS,
+                !self.worker_defense.pulled.contains(w),
+                -(w.hit_points() + w.shields()),
+                w.position().distance(center) as i32,
+            )
+        });
+        let healthy = drones
+            .iter()
+            .filter(|w| w.hit_points() >= MIN_DEFENDER_HIT_POINTS)
+            .count();
+        let frame = self.game.get_frame_count();
+        if threats != self.worker_defense.threats
+            || frame - self.worker_defense.searched_frame >= SEARCH_INTERVAL_FRAMES
+        {
+            self.worker_defense.required =
+                self.drones_required_to_hold(&threats, &defenders, &drones, healthy);
+            self.worker_defense.threats = threats.clone();
+            self.worker_defense.searched_frame = frame;
+        }
+        let Some(required) = self.worker_defense.required.map(|it| it.min(healthy)) else {
+            // Can't hold, don't throw the economy away
+            cvis().log(|| "Worker defense: Can't hold, not pulling");
+            self.release_pulled_workers();
+            return;
+        };
+        let pulled: Vec<_> = drones.drain(..required).collect();
+        for worker in self.worker_defense.pulled.iter() {
+            if !pulled.contains(worker) && worker.exists() {
+                worker.stop().ok();
+            }
+        }
+        for worker in pulled.iter() {
+            self.tracker.reserve_unit(worker);
+            let target = threats
+                .iter()
+                .filter(|e| worker.has_weapon_against(e))
+                .min_by_key(|e| worker.distance_to(*e));
+            if let Some(target) = target {
+                cvis().log_unit_frame(worker, || format!("Defending against {}", target.id()));
+                self.engage(worker, target);
+            }
+        }
+        self.worker_defense.pulled = pulled;
+    }
+}